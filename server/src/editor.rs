@@ -0,0 +1,453 @@
+use std::{collections::HashMap, path::Path};
+
+use bevy::{color::palettes::css, prelude::*, window::PrimaryWindow};
+use strum::{EnumCount, VariantArray};
+use swarm_lib::{
+    BuildingKind,
+    CellKind,
+    FrameKind,
+    Item,
+    Pos,
+    Subsystem,
+    Team,
+};
+
+use crate::{
+    graphics::{
+        tilemap::{MapSize, TilemapWorldCoords},
+        Textures,
+    },
+    levels::{MapFile, SpawnPoint},
+    types::{CellState, GridWorld},
+    GameState,
+    WinCondition,
+};
+
+/// Draws maps by hand and saves them in the format `load-map` reads, for
+/// setting up a scenario without writing a level
+pub struct EditorPlugin {
+    /// `None` unless a map was opened with `--edit`
+    pub map: Option<EditedMap>,
+}
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        let Some(map) = &self.map else {
+            return;
+        };
+        app.insert_resource(map.clone())
+            .init_resource::<Brush>()
+            .add_systems(Startup, open_map)
+            .add_systems(
+                OnEnter(GameState::Editing),
+                (pan_with_right_button, spawn_help),
+            )
+            .add_systems(
+                Update,
+                (
+                    pick_brush,
+                    paint,
+                    save_map,
+                    place_spawn_sprites,
+                    draw_items,
+                    update_help,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Editing)),
+            );
+    }
+}
+
+/// The map file being edited and what it held when opened
+#[derive(Resource, Debug, Clone)]
+pub struct EditedMap {
+    pub path: String,
+    pub map: MapFile,
+}
+
+impl EditedMap {
+    /// Opens the map at `path`, or a blank map of the given size if there is
+    /// no file there yet
+    pub fn open(path: &str, width: usize, height: usize) -> eyre::Result<Self> {
+        let map = if Path::new(path).exists() {
+            MapFile::load(path)?
+        } else {
+            MapFile::blank(width, height)?
+        };
+        Ok(EditedMap {
+            path: path.to_string(),
+            map,
+        })
+    }
+}
+
+/// A bot the map starts with, placed in the editor
+#[derive(Component, Debug, Clone)]
+struct EditorSpawn(SpawnPoint);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Walls,
+    Items,
+    Bots,
+}
+
+/// What clicking on the map places
+#[derive(Resource, Debug, Clone)]
+struct Brush {
+    tool: Tool,
+    item: Item,
+    frame: FrameKind,
+    team: Team,
+    /// The subsystem + and - add and remove
+    subsystem: Subsystem,
+    subsystems: HashMap<Subsystem, u8>,
+    /// The result of the last thing done, such as saving
+    message: String,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Brush {
+            tool: Tool::Walls,
+            item: Item::Metal,
+            frame: FrameKind::Tractor,
+            team: Team::Player,
+            subsystem: Subsystem::CargoBay,
+            subsystems: HashMap::from([(Subsystem::CargoBay, 3)]),
+            message: String::new(),
+        }
+    }
+}
+
+const FRAMES: [FrameKind; 3] = [
+    FrameKind::Flea,
+    FrameKind::Tractor,
+    FrameKind::Building(BuildingKind::Small),
+];
+
+fn open_map(
+    mut commands: Commands,
+    edited: Res<EditedMap>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let map = &edited.map;
+    commands.insert_resource(MapSize {
+        x: map.width() as u32,
+        y: map.height() as u32,
+    });
+    commands.insert_resource(map.grid_world().expect("Checked when opened"));
+    commands.insert_resource(map.win.clone());
+    for spawn in &map.spawn {
+        commands.spawn(EditorSpawn(spawn.clone()));
+    }
+    next_state.set(GameState::Editing);
+}
+
+/// Frees the left button for painting
+fn pan_with_right_button(mut cameras: Query<&mut bevy_pancam::PanCam>) {
+    for mut camera in cameras.iter_mut() {
+        camera.grab_buttons = vec![MouseButton::Right, MouseButton::Middle];
+    }
+}
+
+fn pick_brush(keys: Res<ButtonInput<KeyCode>>, mut brush: ResMut<Brush>) {
+    // Only change the brush when a key is pressed, so that `update_help`
+    // doesn't redraw every frame
+    if keys.get_just_pressed().next().is_none() {
+        return;
+    }
+    let brush = brush.as_mut();
+    if keys.just_pressed(KeyCode::Digit1) {
+        brush.tool = Tool::Walls;
+    }
+    if keys.just_pressed(KeyCode::Digit2) {
+        brush.tool = Tool::Items;
+    }
+    if keys.just_pressed(KeyCode::Digit3) {
+        brush.tool = Tool::Bots;
+    }
+    if keys.just_pressed(KeyCode::KeyI) {
+        let idx = Item::VARIANTS.iter().position(|&i| i == brush.item);
+        let next = idx.map_or(0, |idx| (idx + 1) % Item::VARIANTS.len());
+        brush.item = Item::VARIANTS[next];
+    }
+    if keys.just_pressed(KeyCode::KeyF) {
+        let idx = FRAMES.iter().position(|&frame| frame == brush.frame);
+        brush.frame = FRAMES[idx.map_or(0, |idx| (idx + 1) % FRAMES.len())];
+    }
+    if keys.just_pressed(KeyCode::KeyT) {
+        brush.team = match brush.team {
+            Team::Player => Team::Enemy,
+            Team::Enemy => Team::Player,
+        };
+    }
+    let count = Subsystem::COUNT as u8;
+    if keys.just_pressed(KeyCode::KeyQ) {
+        let prev = (brush.subsystem as u8 + count - 1) % count;
+        brush.subsystem = Subsystem::from_repr(prev).unwrap();
+    }
+    if keys.just_pressed(KeyCode::KeyE) {
+        let next = (brush.subsystem as u8 + 1) % count;
+        brush.subsystem = Subsystem::from_repr(next).unwrap();
+    }
+    if keys.just_pressed(KeyCode::Equal) {
+        *brush.subsystems.entry(brush.subsystem).or_default() += 1;
+    }
+    if keys.just_pressed(KeyCode::Minus) {
+        if let Some(count) = brush.subsystems.get_mut(&brush.subsystem) {
+            *count -= 1;
+            if *count == 0 {
+                brush.subsystems.remove(&brush.subsystem);
+            }
+        }
+    }
+}
+
+/// The cell under the mouse, if it is over the map
+fn hovered_cell(
+    window: &Window,
+    (camera, camera_transform): (&Camera, &GlobalTransform),
+    coords: &TilemapWorldCoords,
+    map_size: &MapSize,
+) -> Option<Pos> {
+    let cursor = window.cursor_position()?;
+    let world = camera.viewport_to_world_2d(camera_transform, cursor).ok()?;
+    coords.world_to_pos(world, map_size)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn paint(
+    mut commands: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    coords: Res<TilemapWorldCoords>,
+    map_size: Res<MapSize>,
+    mut grid_world: ResMut<GridWorld>,
+    spawns: Query<(Entity, &EditorSpawn)>,
+    mut brush: ResMut<Brush>,
+    // The wall tool paints what the first cell clicked was turned into
+    // while the button is held
+    mut painting: Local<Option<CellKind>>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        *painting = None;
+        return;
+    }
+    let (Ok(window), Ok(camera)) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some(pos) = hovered_cell(window, camera, &coords, &map_size) else {
+        return;
+    };
+    let spawn_here = spawns.iter().find(|(_, spawn)| spawn.0.pos == pos.0);
+    let cell = grid_world.get(pos).clone();
+
+    match brush.tool {
+        Tool::Walls => {
+            if spawn_here.is_some() {
+                return;
+            }
+            let paint_with = *painting.get_or_insert(match cell.kind {
+                CellKind::Blocked => CellKind::Empty,
+                _ => CellKind::Blocked,
+            });
+            if cell.kind != paint_with {
+                let state = match paint_with {
+                    CellKind::Blocked => CellState::blocked(),
+                    _ => CellState::empty(),
+                };
+                grid_world.set_tuple(pos.x(), pos.y(), state);
+            }
+        }
+        Tool::Items if mouse.just_pressed(MouseButton::Left) => {
+            if !cell.can_enter() {
+                return;
+            }
+            let item = brush.item;
+            let cell = grid_world.get_mut(pos);
+            cell.item = (cell.item != Some(item)).then_some(item);
+        }
+        Tool::Bots if mouse.just_pressed(MouseButton::Left) => {
+            if let Some((entity, _)) = spawn_here {
+                commands.entity(entity).despawn_recursive();
+                return;
+            }
+            if !cell.can_enter() {
+                return;
+            }
+            let spawn = SpawnPoint {
+                pos: pos.0,
+                team: brush.team,
+                frame: brush.frame,
+                subsystems: brush.subsystems.clone(),
+                inventory: HashMap::new(),
+                energy: None,
+            };
+            match spawn.check_loadout() {
+                Ok(()) => {
+                    commands.spawn(EditorSpawn(spawn));
+                }
+                Err(err) => brush.message = format!("{err:#}"),
+            }
+        }
+        Tool::Items | Tool::Bots => {}
+    }
+}
+
+fn save_map(
+    keys: Res<ButtonInput<KeyCode>>,
+    edited: Res<EditedMap>,
+    grid_world: Res<GridWorld>,
+    win_condition: Res<WinCondition>,
+    spawns: Query<&EditorSpawn>,
+    mut brush: ResMut<Brush>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !(ctrl && keys.just_pressed(KeyCode::KeyS)) {
+        return;
+    }
+    let mut spawn = spawns
+        .iter()
+        .map(|spawn| spawn.0.clone())
+        .collect::<Vec<_>>();
+    // Keep the file the same when nothing moved
+    spawn.sort_by_key(|spawn| (spawn.pos.1, spawn.pos.0));
+    // The editor can't draw regions yet, so keep the ones the file had
+    let map = MapFile {
+        region: edited.map.region.clone(),
+        ..MapFile::new(&grid_world, spawn, win_condition.clone())
+    };
+    brush.message = match map.save(&edited.path) {
+        Ok(()) => format!("Saved to {}", edited.path),
+        Err(err) => format!("Could not save: {err:#}"),
+    };
+    info!("{}", brush.message);
+}
+
+fn team_color(team: Team) -> Color {
+    match team {
+        Team::Player => css::DODGER_BLUE.into(),
+        Team::Enemy => css::TOMATO.into(),
+    }
+}
+
+fn place_spawn_sprites(
+    mut commands: Commands,
+    spawns: Query<(Entity, &EditorSpawn, Option<&Sprite>)>,
+    mut transforms: Query<&mut Transform, With<EditorSpawn>>,
+    textures: Res<Textures>,
+    coords: Res<TilemapWorldCoords>,
+) {
+    for (entity, spawn, sprite) in spawns.iter() {
+        let world = coords.pos_to_world(&Pos(spawn.0.pos));
+        if sprite.is_none() {
+            let mut sprite = Sprite::from_atlas_image(
+                textures.pawns.0.clone(),
+                TextureAtlas {
+                    layout: textures.pawns.1.clone(),
+                    index: match spawn.0.frame {
+                        FrameKind::Flea => 0,
+                        FrameKind::Tractor => 1,
+                        FrameKind::Building(BuildingKind::Small) => 2,
+                    },
+                },
+            );
+            sprite.color = team_color(spawn.0.team);
+            commands.entity(entity).insert((
+                sprite,
+                Transform::from_xyz(world.x, world.y, 1.0)
+                    .with_scale(Vec3::new(0.5, 0.5, 1.0)),
+            ));
+        } else if let Ok(mut transform) = transforms.get_mut(entity) {
+            // The map moves until its first frame has been laid out
+            transform.translation.x = world.x;
+            transform.translation.y = world.y;
+        }
+    }
+}
+
+/// Marks the items the tilemap has no tiles for
+fn draw_items(
+    mut gizmos: Gizmos,
+    grid_world: Res<GridWorld>,
+    coords: Res<TilemapWorldCoords>,
+) {
+    for ((x, y), cell) in grid_world.iter() {
+        let color = match cell.item {
+            Some(Item::Crumb) => css::GOLDENROD,
+            Some(Item::Fent) => css::MAGENTA,
+            Some(Item::Truffle) => css::SADDLE_BROWN,
+            Some(Item::Metal) | None => continue,
+        };
+        let world = coords.pos_to_world(&Pos((x, y)));
+        gizmos.circle_2d(Isometry2d::from_translation(world), 10.0, color);
+    }
+}
+
+#[derive(Component)]
+struct HelpText;
+
+fn spawn_help(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                top: Val::Px(10.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.15, 0.15, 0.15, 0.8)),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                HelpText,
+            ));
+        });
+}
+
+fn update_help(
+    brush: Res<Brush>,
+    edited: Res<EditedMap>,
+    mut text: Query<&mut Text, With<HelpText>>,
+) {
+    if !brush.is_changed() {
+        return;
+    }
+    let Ok(mut text) = text.get_single_mut() else {
+        return;
+    };
+    let tool = match brush.tool {
+        Tool::Walls => "walls",
+        Tool::Items => "items",
+        Tool::Bots => "bots",
+    };
+    let mut subsystems = brush
+        .subsystems
+        .iter()
+        .map(|(subsystem, count)| format!("{subsystem:?} {count}"))
+        .collect::<Vec<_>>();
+    subsystems.sort();
+    text.0 = format!(
+        "Editing {path}\n1 walls, 2 items, 3 bots. Left click paints, right \
+         drag pans, Ctrl+S saves\nPainting {tool}\nI item: {item:?}\nF frame: \
+         {frame}, T team: {team}\nQ/E subsystem: {subsystem:?}, +/- adds or \
+         removes one\nSubsystems: {subsystems}\n{message}",
+        path = edited.path,
+        item = brush.item,
+        frame = brush.frame,
+        team = brush.team,
+        subsystem = brush.subsystem,
+        subsystems = subsystems.join(", "),
+        message = brush.message,
+    );
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use dlopen2::wrapper::{Container, WrapperApi};
+use eyre::bail;
+use serde::{Deserialize, Serialize};
+use swarm_lib::{
+    bot_logger::BotLogger,
+    Bot,
+    BotConfig,
+    MatchInfo,
+    Rules,
+    Team,
+};
+
+use crate::{
+    game::{
+        bot_update::{BotId, BotInstance, MAX_BOT_CRASHES},
+        core::CoreSystemsSet,
+        time_budget::TimeBudget,
+    },
+    replay::LiveOrReplay,
+    types::Seed,
+    WinCondition,
+};
+
+pub const DEFAULT_BOT_LIB_PATH: &str =
+    "/Users/jh/personal/swarm-skirmish/target/debug/libsimple_bots.dylib";
+
+#[derive(WrapperApi)]
+pub struct Api {
+    new_bot: fn(bot_logger: BotLogger, config: BotConfig) -> Box<dyn Bot>,
+}
+
+/// The currently loaded bot library.
+///
+/// `path` is the library the user pointed us at, `loaded_path` is the file
+/// that is actually open. They differ after a hot reload, because the dynamic
+/// loader hands back the already loaded library if asked to open the same
+/// path twice.
+#[derive(Resource)]
+pub struct BotLib {
+    pub container: Container<Api>,
+    pub path: PathBuf,
+    loaded_path: PathBuf,
+    modified: Option<SystemTime>,
+    pending_modified: Option<SystemTime>,
+    pub generation: u32,
+    /// Hash of the loaded library file
+    pub hash: u64,
+}
+
+impl BotLib {
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let container: Container<Api> = unsafe { Container::load(&path) }
+            .expect("Could not open library or load symbols");
+
+        BotLib {
+            container,
+            modified: modified_time(&path),
+            pending_modified: None,
+            hash: file_hash(&path),
+            loaded_path: path.clone(),
+            path,
+            generation: 0,
+        }
+    }
+
+    pub fn new_bot(&self, bot_id: u32, config: BotConfig) -> Box<dyn Bot> {
+        self.container.new_bot(BotLogger::new(bot_id), config)
+    }
+
+    pub fn identity(&self) -> BotLibIdentity {
+        BotLibIdentity {
+            path: self.path.clone(),
+            generation: self.generation,
+            hash: self.hash,
+        }
+    }
+}
+
+/// How the match was started, for the `BotConfig` each bot is created with
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MatchSetup {
+    /// The name of the level, empty if the match didn't start from one
+    pub level: String,
+    pub seed: Seed,
+    /// From `--bot-param key=value`
+    pub bot_params: BTreeMap<String, String>,
+    pub rules: Rules,
+}
+
+impl MatchSetup {
+    pub fn bot_config(
+        &self,
+        bot_id: BotId,
+        team: Team,
+        (map_width, map_height): (usize, usize),
+        match_info: Arc<MatchInfo>,
+    ) -> BotConfig {
+        // Mixed with the match seed so that bots with the same id play
+        // differently in different matches
+        let seed_bytes =
+            [self.seed.0.to_le_bytes(), u64::from(bot_id.0).to_le_bytes()];
+        BotConfig {
+            team,
+            map_width,
+            map_height,
+            level: self.level.clone(),
+            rules: self.rules.clone(),
+            seed: fnv1a(seed_bytes.as_flattened()),
+            params: self.bot_params.clone(),
+            match_info,
+        }
+    }
+}
+
+/// The rules as the bots are told them
+pub fn rules(win_condition: &WinCondition, time_budget: &TimeBudget) -> Rules {
+    Rules {
+        win_hold: win_condition.hold.clone(),
+        tick_budget: time_budget.per_tick,
+        time_bank: time_budget.bank,
+        max_crashes: MAX_BOT_CRASHES,
+    }
+}
+
+/// Parses `key=value` pairs, as given to `--bot-param`
+pub fn parse_bot_params(
+    params: &[String],
+) -> eyre::Result<BTreeMap<String, String>> {
+    params
+        .iter()
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                Ok((key.to_string(), value.to_string()))
+            }
+            _ => bail!("Bot parameter {param:?} isn't of the form key=value"),
+        })
+        .collect()
+}
+
+/// Which build of a bot library was running, e.g. when a replay was recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BotLibIdentity {
+    pub path: PathBuf,
+    pub generation: u32,
+    /// FNV-1a hash of the library file
+    pub hash: u64,
+}
+
+/// Sent after the bot library was reloaded and all bots were re-created
+#[derive(Event, Debug, Clone)]
+pub struct BotLibReloaded {
+    pub path: PathBuf,
+    pub generation: u32,
+    pub bots_reloaded: usize,
+}
+
+pub struct BotLibPlugin {
+    pub bot_lib_path: String,
+}
+
+impl Plugin for BotLibPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BotLib::load(&self.bot_lib_path))
+            .add_event::<BotLibReloaded>()
+            .add_systems(
+                Update,
+                hot_reload_bot_lib
+                    .before(CoreSystemsSet)
+                    .run_if(in_state(LiveOrReplay::Live))
+                    .run_if(on_timer(Duration::from_secs(1))),
+            );
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Returns 0 if the file can't be read
+fn file_hash(path: &Path) -> u64 {
+    let Ok(bytes) = std::fs::read(path) else {
+        return 0;
+    };
+    fnv1a(&bytes)
+}
+
+/// FNV-1a, so the hash stays the same across builds of the server
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Polls the bot library file and swaps in the new build between ticks.
+///
+/// Every bot is re-created through `new_bot`, with the config it was first
+/// created with. If the old instance implements [`Bot::save_state`], the
+/// state is handed to the new instance via [`Bot::restore_state`].
+fn hot_reload_bot_lib(
+    mut bot_lib: ResMut<BotLib>,
+    mut bots: Query<(&BotId, &mut BotInstance)>,
+    mut reloaded: EventWriter<BotLibReloaded>,
+) {
+    let modified = modified_time(&bot_lib.path);
+    if modified.is_none() || modified == bot_lib.modified {
+        bot_lib.pending_modified = None;
+        return;
+    }
+
+    // Wait until the file stops changing so we don't open a library that
+    // cargo is still writing
+    if bot_lib.pending_modified != modified {
+        bot_lib.pending_modified = modified;
+        return;
+    }
+    bot_lib.modified = modified;
+    bot_lib.pending_modified = None;
+
+    let generation = bot_lib.generation + 1;
+    let reload_path = reload_copy_path(&bot_lib.path, generation);
+    if let Err(err) = std::fs::copy(&bot_lib.path, &reload_path) {
+        warn!(?err, path = ?reload_path, "Failed to copy bot library");
+        return;
+    }
+
+    let container: Container<Api> = match unsafe {
+        Container::load(&reload_path)
+    } {
+        Ok(container) => container,
+        Err(err) => {
+            warn!(?err, path = ?reload_path, "Failed to reload bot library");
+            let _ = std::fs::remove_file(&reload_path);
+            return;
+        }
+    };
+
+    let mut bots_reloaded = 0;
+    for (bot_id, mut bot_instance) in bots.iter_mut() {
+        let state = bot_instance.bot.save_state();
+        let config = bot_instance.config.clone();
+        let mut bot = container.new_bot(BotLogger::new(bot_id.0), config);
+        if let Some(state) = state {
+            bot.restore_state(state);
+        }
+
+        // The old bot is dropped here, while its library is still loaded
+        bot_instance.bot = bot;
+        bots_reloaded += 1;
+    }
+
+    // Only now is it safe to unload the old library
+    let old_loaded_path =
+        std::mem::replace(&mut bot_lib.loaded_path, reload_path);
+    bot_lib.hash = file_hash(&bot_lib.loaded_path);
+    bot_lib.container = container;
+    bot_lib.generation = generation;
+    if old_loaded_path != bot_lib.path {
+        let _ = std::fs::remove_file(old_loaded_path);
+    }
+
+    info!(
+        generation,
+        bots_reloaded,
+        path = ?bot_lib.path,
+        "Reloaded bot library"
+    );
+    reloaded.send(BotLibReloaded {
+        path: bot_lib.path.clone(),
+        generation,
+        bots_reloaded,
+    });
+}
+
+fn reload_copy_path(path: &Path, generation: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("bot_lib");
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    std::env::temp_dir().join(format!(
+        "{stem}-{pid}-reload-{generation}.{extension}",
+        pid = std::process::id()
+    ))
+}
@@ -1,49 +1,143 @@
-use bevy::{prelude::*, utils::HashMap};
-use dlopen2::wrapper::{Container, WrapperApi};
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::{component::Tick as ChangeTick, world::DeferredWorld},
+    prelude::*,
+    utils::HashMap,
+};
+use cpu_time::ThreadTime;
 use serde::{Deserialize, Serialize};
 use swarm_lib::{
-    bot_logger::{BotLogger, LogEntry},
+    bot_logger::LogEntry,
     known_map::{ClientBotData, KnownMap},
     Action,
     ActionResult,
     ActionStatus,
     ActionWithId,
     Bot,
+    BotConfig,
     BotData,
     BotUpdate,
     CellKind,
     Item,
+    MatchInfo,
     Pos,
+    Team,
+    SERVER_ACTION_ID,
 };
 use ustr::ustr;
 
 use crate::{
-    game::apply_actions::{
-        ActionContainer,
-        ActionState,
-        CurrentAction,
-        PastActions,
+    game::{
+        apply_actions::{
+            ActionContainer,
+            ActionState,
+            CurrentAction,
+            PastActions,
+        },
+        bot_lib::{BotLib, MatchSetup},
+        time_budget::{
+            BotTimeStats,
+            BudgetEnforcement,
+            TeamTimeStats,
+            TimeBudget,
+        },
     },
+    graphics::tilemap::MapSize,
+    replay::LiveOrReplay,
     types::{GridWorld, Tick},
 };
 
-#[derive(WrapperApi)]
-struct Api {
-    new_bot: fn(bot_logger: BotLogger) -> Box<dyn Bot>,
-}
-
-#[derive(Resource)]
-struct BotLib(pub Container<Api>);
-
 #[derive(
     Component, Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize,
 )]
-#[require(CurrentAction, PastActions, BotLogs)]
+#[require(CurrentAction, PastActions, BotLogs, BotCrashes, BotTimeStats)]
 pub struct BotId(pub u32);
 
 #[derive(Component, Default, Serialize, Deserialize, Clone)]
 pub struct BotLogs(pub Vec<LogEntry>);
 
+/// Number of panics after which a bot is no longer updated
+pub const MAX_BOT_CRASHES: usize = 3;
+
+/// Number of log entries kept with each crash, counting back from the last
+const CRASH_LOG_ENTRIES: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotCrash {
+    pub tick: u32,
+    pub message: String,
+    /// The last [`CRASH_LOG_ENTRIES`] logs from the bot's last successful
+    /// update
+    pub logs: Vec<LogEntry>,
+}
+
+/// Panics caught while updating a bot
+#[derive(Component, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BotCrashes {
+    pub crashes: Vec<BotCrash>,
+    pub disabled: bool,
+}
+
+impl BotCrashes {
+    pub fn last_message(&self) -> Option<&str> {
+        self.crashes.last().map(|crash| crash.message.as_str())
+    }
+}
+
+/// Sent for every action a bot submits, in `BotId` order. This includes the
+/// Noops forced on bots that crashed or went over their time budget, so the
+/// actions alone are enough to re-simulate a match.
+#[derive(Event, Debug, Clone)]
+pub struct ActionSubmitted {
+    pub bot_id: BotId,
+    pub action: ActionContainer,
+}
+
+/// Sent when the action a bot returned is thrown away because the update
+/// went over the time budget, so that input replays can skip it too
+#[derive(Event, Debug, Clone)]
+pub struct ActionSkipped {
+    pub bot_id: BotId,
+}
+
+/// Sent whenever a bot panics inside `Bot::update`
+#[derive(Event, Debug, Clone)]
+pub struct BotCrashed {
+    pub bot_id: BotId,
+    pub tick: u32,
+    pub message: String,
+    pub disabled: bool,
+}
+
+/// One line per crashed bot, for the UI and match results
+pub fn crash_summary<'a>(
+    bots: impl IntoIterator<Item = (&'a BotId, &'a BotCrashes)>,
+) -> Vec<String> {
+    let mut crashed = bots
+        .into_iter()
+        .filter(|(_, crashes)| !crashes.crashes.is_empty())
+        .collect::<Vec<_>>();
+    crashed.sort_by_key(|(bot_id, _)| bot_id.0);
+
+    crashed
+        .into_iter()
+        .map(|(bot_id, crashes)| {
+            format!(
+                "Bot {} crashed {}x{}: {}",
+                bot_id.0,
+                crashes.crashes.len(),
+                if crashes.disabled { " (disabled)" } else { "" },
+                crashes.last_message().unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
 pub struct BotUpdatePlugin;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
@@ -54,23 +148,26 @@ pub struct NextBotId(pub u32);
 
 impl Plugin for BotUpdatePlugin {
     fn build(&self, app: &mut App) {
-        let cont: Container<Api> = unsafe {
-            Container::load(
-                "/Users/jh/personal/swarm-skirmish/target/debug/\
-                 libsimple_bots.dylib",
-            )
-        }
-        .expect("Could not open library or load symbols");
-
         app.add_systems(
             Update,
-            (update_known_maps, update_bots)
+            (
+                update_known_maps,
+                update_bots.run_if(in_state(LiveOrReplay::Live)),
+            )
                 .chain()
                 .in_set(BotUpdateSystemSet),
         )
-        .insert_resource(BotLib(cont))
         .insert_resource(NextBotId(0))
-        .init_resource::<BotIdToEntity>();
+        .init_resource::<BotIdToEntity>()
+        .init_resource::<TeamTimeStats>()
+        // Levels that name parts of the map replace it
+        .init_resource::<MatchInfo>()
+        .init_resource::<SharedMatchInfo>()
+        // The server replaces it with how the match was started
+        .init_resource::<MatchSetup>()
+        .add_event::<BotCrashed>()
+        .add_event::<ActionSubmitted>()
+        .add_event::<ActionSkipped>();
 
         app.world_mut()
             .register_component_hooks::<BotData>()
@@ -101,16 +198,17 @@ impl Plugin for BotUpdatePlugin {
 
                 if world.entity(entity).get::<BotInstance>().is_none() {
                     info!("Creating new bot instance for bot ID: {}", bot_id.0);
+                    let match_info = shared_match_info(&mut world);
+                    let config = bot_config(&world, entity, bot_id, match_info);
                     let bot = world
                         .resource::<BotLib>()
-                        .0
-                        .new_bot(BotLogger::new(bot_id.0));
+                        .new_bot(bot_id.0, config.clone());
 
                     // Insert the bot ID and instance into the entity
                     world
                         .commands()
                         .entity(entity)
-                        .insert((bot_id, BotInstance { bot }));
+                        .insert((bot_id, BotInstance { bot, config }));
                 } else {
                     // Insert the bot ID into the entity
                     world.commands().entity(entity).insert(bot_id);
@@ -119,9 +217,70 @@ impl Plugin for BotUpdatePlugin {
     }
 }
 
+/// `MatchInfo` in the `Arc` the bots share, so that it is only copied when
+/// the resource changes rather than on every tick
+#[derive(Resource, Default)]
+struct SharedMatchInfo {
+    match_info: Arc<MatchInfo>,
+    /// When `MatchInfo` had last changed as of the copy
+    changed: Option<ChangeTick>,
+}
+
+impl SharedMatchInfo {
+    fn get(
+        &mut self,
+        match_info: &MatchInfo,
+        changed: ChangeTick,
+    ) -> Arc<MatchInfo> {
+        if self.changed != Some(changed) {
+            self.match_info = Arc::new(match_info.clone());
+            self.changed = Some(changed);
+        }
+        self.match_info.clone()
+    }
+}
+
+/// [`SharedMatchInfo::get`] for hooks, which can't borrow two resources at
+/// once
+fn shared_match_info(world: &mut DeferredWorld) -> Arc<MatchInfo> {
+    let changed = world
+        .get_resource_change_ticks::<MatchInfo>()
+        .expect("Initialised by the plugin")
+        .changed;
+    let mut shared =
+        std::mem::take(&mut *world.resource_mut::<SharedMatchInfo>());
+    let match_info = shared.get(world.resource::<MatchInfo>(), changed);
+    *world.resource_mut::<SharedMatchInfo>() = shared;
+    match_info
+}
+
+/// The config for the bot of `entity`, from the match it is created in
+fn bot_config(
+    world: &World,
+    entity: Entity,
+    bot_id: BotId,
+    match_info: Arc<MatchInfo>,
+) -> BotConfig {
+    let team = world
+        .entity(entity)
+        .get::<BotData>()
+        .expect("Just added")
+        .team;
+    let map_size = world.resource::<MapSize>();
+    world.resource::<MatchSetup>().bot_config(
+        bot_id,
+        team,
+        (map_size.x as usize, map_size.y as usize),
+        match_info,
+    )
+}
+
 #[derive(Component)]
 pub struct BotInstance {
     pub bot: Box<dyn Bot>,
+    /// What the bot was created with, and is created with again after a hot
+    /// reload
+    pub config: BotConfig,
 }
 
 #[derive(Resource, Default)]
@@ -141,8 +300,23 @@ impl BotIdToEntity {
     }
 }
 
+/// Effects of one bot update on state shared between bots
+struct BotUpdateReport {
+    bot_id: BotId,
+    team: Team,
+    elapsed: Duration,
+    crash: Option<BotCrashed>,
+    submitted: Option<ActionContainer>,
+    skipped: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn update_bots(
     tick: Res<Tick>,
+    time_budget: Res<TimeBudget>,
+    match_info: Res<MatchInfo>,
+    mut shared_match_info: ResMut<SharedMatchInfo>,
+    mut team_time: ResMut<TeamTimeStats>,
     // mut updates: In<HashMap<BotId, BotUpdate>>,
     mut query: Query<(
         Entity,
@@ -152,95 +326,247 @@ fn update_bots(
         &mut PastActions,
         &mut BotInstance,
         &mut BotLogs,
+        &mut BotCrashes,
+        &mut BotTimeStats,
     )>,
+    mut crashed: EventWriter<BotCrashed>,
+    mut submitted: EventWriter<ActionSubmitted>,
+    mut skipped: EventWriter<ActionSkipped>,
 ) {
-    for (
-        entity,
-        bot_id,
-        bot_data,
-        mut current_action,
-        mut past_actions,
-        mut bot_instance,
-        mut bot_logs,
-    ) in query.iter_mut()
-    {
-        debug!(?bot_id, entity = entity.index(), "Updating bot");
-        // let server_update = updates.remove(bot_id).unwrap();
-
-        let server_update = BotUpdate {
-            tick: tick.0,
-            in_progress_action: {
-                current_action.as_ref().as_ref().map(|action_container| {
-                    ActionWithId {
-                        action: action_container.kind.clone(),
-                        id: action_container.id,
-                        reason: action_container.reason.as_str(),
-                    }
-                })
-            },
-            completed_action: {
-                past_actions.last().and_then(|action| {
-                    if action.completed_tick == tick.0 {
-                        Some(ActionResult {
-                            action: action.action.clone(),
-                            status: action.status.clone(),
-                            id: action.id,
-                            reason: action.reason,
-                            completed_tick: tick.0,
+    // Bots only touch their own components, so they can be updated in
+    // parallel. Anything shared is reported back and applied afterwards in
+    // `BotId` order, so the outcome doesn't depend on thread scheduling.
+    let reports = Mutex::new(Vec::new());
+    let match_info =
+        shared_match_info.get(&match_info, match_info.last_changed());
+    query.par_iter_mut().for_each(
+        |(
+            entity,
+            bot_id,
+            bot_data,
+            mut current_action,
+            mut past_actions,
+            mut bot_instance,
+            mut bot_logs,
+            mut bot_crashes,
+            mut time_stats,
+        )| {
+            let mut report = BotUpdateReport {
+                bot_id: *bot_id,
+                team: bot_data.team,
+                elapsed: Duration::ZERO,
+                crash: None,
+                submitted: None,
+                skipped: false,
+            };
+            let maybe_action = if bot_crashes.disabled {
+                // A disabled bot stops whatever it was doing instead of
+                // carrying it on forever
+                if current_action.0.is_none() {
+                    return;
+                }
+                Some(server_noop("Bot disabled"))
+            } else if std::mem::take(&mut time_stats.penalized) {
+                debug!(?bot_id, "Forcing Noop after going over time budget");
+                Some(server_noop("Over time budget"))
+            } else {
+                debug!(?bot_id, entity = entity.index(), "Updating bot");
+                let server_update = BotUpdate {
+                    tick: tick.0,
+                    in_progress_action: {
+                        current_action.as_ref().as_ref().map(
+                            |action_container| ActionWithId {
+                                action: action_container.kind.clone(),
+                                id: action_container.id,
+                                reason: action_container.reason.as_str(),
+                            },
+                        )
+                    },
+                    completed_action: {
+                        past_actions.last().and_then(|action| {
+                            if action.completed_tick == tick.0 {
+                                Some(ActionResult {
+                                    action: action.action.clone(),
+                                    status: action.status.clone(),
+                                    id: action.id,
+                                    reason: action.reason,
+                                    completed_tick: tick.0,
+                                })
+                            } else {
+                                None
+                            }
                         })
-                    } else {
-                        None
+                    },
+                    // Cheap, the known map is shared rather than copied
+                    bot_data: bot_data.clone(),
+                    match_info: match_info.clone(),
+                };
+
+                let bot = &mut bot_instance.bot;
+                let start = Instant::now();
+                let cpu_start = ThreadTime::now();
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    bot.update(server_update)
+                }));
+                let elapsed = cpu_start.elapsed();
+                let wall = start.elapsed();
+
+                let over_budget =
+                    time_stats.record(elapsed, wall, &time_budget);
+
+                let (maybe_action, logs) = match result {
+                    Ok(result) => result,
+                    Err(payload) => {
+                        let message = panic_message(payload.as_ref());
+                        let first_log =
+                            bot_logs.0.len().saturating_sub(CRASH_LOG_ENTRIES);
+                        bot_crashes.crashes.push(BotCrash {
+                            tick: tick.0,
+                            message: message.clone(),
+                            logs: bot_logs.0[first_log..].to_vec(),
+                        });
+                        bot_crashes.disabled =
+                            bot_crashes.crashes.len() >= MAX_BOT_CRASHES;
+                        error!(
+                            ?bot_id,
+                            %message,
+                            disabled = bot_crashes.disabled,
+                            "Bot panicked during update"
+                        );
+                        report.crash = Some(BotCrashed {
+                            bot_id: *bot_id,
+                            tick: tick.0,
+                            message,
+                            disabled: bot_crashes.disabled,
+                        });
+
+                        (Some(server_noop("Bot crashed")), bot_logs.0.clone())
                     }
-                })
-            },
-            bot_data: bot_data.clone(),
-        };
-
-        let (maybe_action, logs) =
-            bot_instance.bot.update(server_update.clone());
-
-        bot_logs.0 = logs;
-
-        let Some(action) = maybe_action else {
-            debug!("No action from bot ID: {}", bot_id.0);
-            continue;
-        };
-
-        trace!("Bot ID: {} action: {:?}", bot_id.0, action);
-        let action_container = ActionContainer {
-            reason: ustr(action.reason),
-            state: match &action.action {
-                Action::MoveTo(path) => ActionState::MoveTo {
-                    idx: 1.min(path.len().saturating_sub(1)),
-                },
-                Action::Noop => ActionState::None,
-                Action::MoveDir(_) => ActionState::None,
-                Action::Harvest(_) => ActionState::None,
-                Action::Pickup(_) => ActionState::None,
-                Action::Drop(_) => ActionState::None,
-                Action::Transfer(_) => ActionState::None,
-                Action::Build(_dir, _building_kind, _subsystems) => {
-                    ActionState::None
+                };
+
+                bot_logs.0 = logs;
+                report.elapsed = elapsed;
+
+                if over_budget {
+                    warn!(
+                        ?bot_id,
+                        ?elapsed,
+                        enforcement = ?time_budget.enforcement,
+                        "Bot went over its time budget"
+                    );
+                    match time_budget.enforcement {
+                        BudgetEnforcement::Off => maybe_action,
+                        BudgetEnforcement::SkipAction => {
+                            report.skipped = maybe_action.is_some();
+                            None
+                        }
+                        BudgetEnforcement::NoopNextTick => {
+                            time_stats.penalized = true;
+                            maybe_action
+                        }
+                    }
+                } else {
+                    maybe_action
+                }
+            };
+
+            let action_container = maybe_action.map(|action| {
+                trace!("Bot ID: {} action: {:?}", bot_id.0, action);
+                ActionContainer {
+                    reason: ustr(action.reason),
+                    state: match &action.action {
+                        Action::MoveTo(path) => ActionState::MoveTo {
+                            idx: 1.min(path.len().saturating_sub(1)),
+                        },
+                        Action::Noop => ActionState::None,
+                        Action::MoveDir(_) => ActionState::None,
+                        Action::Harvest(_) => ActionState::None,
+                        Action::Pickup(_) => ActionState::None,
+                        Action::Drop(_) => ActionState::None,
+                        Action::Transfer(_) => ActionState::None,
+                        Action::Build(_dir, _building_kind, _subsystems) => {
+                            ActionState::None
+                        }
+                        Action::Recharge(_dir) => ActionState::None,
+                        Action::Attack(_dir) => ActionState::None,
+                        Action::Msg { .. } => ActionState::None,
+                        Action::ShareMap { .. } => ActionState::None,
+                    },
+                    kind: action.action,
+                    id: action.id,
                 }
-                Action::Recharge(_dir) => ActionState::None,
-                Action::Attack(_dir) => ActionState::None,
-                Action::Msg { .. } => ActionState::None,
-                Action::ShareMap { .. } => ActionState::None,
-            },
-            kind: action.action,
-            id: action.id,
-        };
-
-        // If there is a current action already, cancel it
-        if let Some(action) = current_action.0.replace(action_container) {
-            past_actions.push(ActionResult {
-                action: action.kind,
-                id: action.id,
-                reason: action.reason,
-                status: ActionStatus::Cancelled,
-                completed_tick: server_update.tick,
             });
+            report.submitted = action_container.clone();
+            reports.lock().unwrap().push(report);
+
+            let Some(action_container) = action_container else {
+                debug!("No action from bot ID: {}", bot_id.0);
+                return;
+            };
+            submit_action(
+                &mut current_action,
+                &mut past_actions,
+                action_container,
+                tick.0,
+            );
+        },
+    );
+
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|report| report.bot_id.0);
+    for report in reports {
+        team_time.add(report.team, report.elapsed);
+        if let Some(crash) = report.crash {
+            crashed.send(crash);
         }
+        if report.skipped {
+            skipped.send(ActionSkipped {
+                bot_id: report.bot_id,
+            });
+        }
+        if let Some(action) = report.submitted {
+            submitted.send(ActionSubmitted {
+                bot_id: report.bot_id,
+                action,
+            });
+        }
+    }
+}
+
+/// A Noop submitted by the server on behalf of a bot
+fn server_noop(reason: &'static str) -> ActionWithId {
+    ActionWithId {
+        id: SERVER_ACTION_ID,
+        action: Action::Noop,
+        reason,
+    }
+}
+
+/// Makes `action` the bot's current action, cancelling the one in progress
+pub fn submit_action(
+    current_action: &mut CurrentAction,
+    past_actions: &mut PastActions,
+    action: ActionContainer,
+    tick: u32,
+) {
+    if let Some(action) = current_action.0.replace(action) {
+        past_actions.push(ActionResult {
+            action: action.kind,
+            id: action.id,
+            reason: action.reason,
+            status: ActionStatus::Cancelled,
+            completed_tick: tick,
+        });
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "<non-string panic payload>".to_string()
     }
 }
 
@@ -31,7 +31,7 @@ impl Plugin for CorePlugin {
             )
             .configure_sets(
                 Update,
-                CoreSystemsSet.run_if(in_state(LiveOrReplay::Live)),
+                CoreSystemsSet.run_if(not(in_state(LiveOrReplay::Replay))),
             )
             .add_systems(
                 Update,
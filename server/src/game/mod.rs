@@ -1,3 +1,5 @@
 pub mod apply_actions;
+pub mod bot_lib;
 pub mod bot_update;
 pub mod core;
+pub mod time_budget;
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use argh::FromArgValue;
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use swarm_lib::Team;
+
+/// What happens to a bot that spends more than its per tick budget after its
+/// time bank has run out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BudgetEnforcement {
+    /// Time is only measured. Even CPU time varies from run to run, so
+    /// enforcing the budget lets the same seed play out differently.
+    #[default]
+    Off,
+    /// The action returned by the slow update is thrown away
+    SkipAction,
+    /// The action is kept, but the bot is not updated on the next tick and
+    /// does a Noop instead
+    NoopNextTick,
+}
+
+impl FromArgValue for BudgetEnforcement {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        Ok(match value {
+            "off" => BudgetEnforcement::Off,
+            "skip-action" => BudgetEnforcement::SkipAction,
+            "noop-next-tick" => BudgetEnforcement::NoopNextTick,
+            _ => return Err(format!("Invalid budget enforcement: {}", value)),
+        })
+    }
+}
+
+/// Limits on how long a single `Bot::update` call may take
+#[derive(Resource, Debug, Clone)]
+pub struct TimeBudget {
+    /// CPU time a bot may spend in one update without touching its bank
+    pub per_tick: Duration,
+    /// Time a bot may spend over `per_tick`, summed over the whole match
+    pub bank: Duration,
+    pub enforcement: BudgetEnforcement,
+}
+
+/// Time a bot has spent in `Bot::update`. Everything but `last_wall` is CPU
+/// time of the thread running the update, so that bots updated in parallel
+/// aren't charged for waiting on each other's threads. The budget applies to
+/// CPU time.
+#[derive(Component, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BotTimeStats {
+    pub last: Duration,
+    /// Wall clock time of the last update, which also counts time the thread
+    /// wasn't scheduled, so it depends on how busy the machine is
+    pub last_wall: Duration,
+    pub max: Duration,
+    pub total: Duration,
+    pub updates: u32,
+    /// Time spent over the per tick budget
+    pub bank_used: Duration,
+    /// Number of updates that went over budget with an empty bank
+    pub overruns: u32,
+    /// The bot does a Noop instead of being updated on its next tick
+    pub penalized: bool,
+}
+
+impl BotTimeStats {
+    pub fn bank_remaining(&self, budget: &TimeBudget) -> Duration {
+        budget.bank.saturating_sub(self.bank_used)
+    }
+
+    /// Records one update and returns true if the bot should be penalised
+    pub fn record(
+        &mut self,
+        elapsed: Duration,
+        wall: Duration,
+        budget: &TimeBudget,
+    ) -> bool {
+        self.last = elapsed;
+        self.last_wall = wall;
+        self.max = self.max.max(elapsed);
+        self.total += elapsed;
+        self.updates += 1;
+
+        let excess = elapsed.saturating_sub(budget.per_tick);
+        if excess.is_zero() {
+            return false;
+        }
+
+        let over_budget = excess > self.bank_remaining(budget);
+        self.bank_used += excess;
+        if over_budget {
+            self.overruns += 1;
+        }
+        over_budget
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.updates == 0 {
+            return Duration::ZERO;
+        }
+        self.total / self.updates
+    }
+}
+
+/// Total CPU time spent in `Bot::update` by all bots of a team, including
+/// bots that have since been despawned
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TeamTimeStats(pub HashMap<Team, Duration>);
+
+impl TeamTimeStats {
+    pub fn get(&self, team: Team) -> Duration {
+        self.0.get(&team).copied().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, team: Team, elapsed: Duration) {
+        *self.0.entry(team).or_default() += elapsed;
+    }
+}
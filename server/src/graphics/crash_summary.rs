@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+
+use super::notifications::{spawn_notification, NotificationArea};
+use crate::game::bot_update::{crash_summary, BotCrashed, BotCrashes, BotId};
+
+pub struct CrashSummaryPlugin;
+
+impl Plugin for CrashSummaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (notify_bot_crashed, update_crash_summary));
+    }
+}
+
+#[derive(Component)]
+struct CrashSummaryPanel;
+
+fn notify_bot_crashed(
+    mut commands: Commands,
+    mut events: EventReader<BotCrashed>,
+    area: Query<Entity, With<NotificationArea>>,
+) {
+    let Ok(area) = area.get_single() else {
+        return;
+    };
+    for event in events.read() {
+        let disabled = if event.disabled { ", disabled" } else { "" };
+        spawn_notification(
+            &mut commands,
+            area,
+            format!(
+                "Bot {} crashed at tick {}{}: {}",
+                event.bot_id.0, event.tick, disabled, event.message
+            ),
+        );
+    }
+}
+
+/// Keeps a panel in the bottom left corner listing every bot that crashed
+fn update_crash_summary(
+    mut commands: Commands,
+    bots: Query<(&BotId, Ref<BotCrashes>)>,
+    panel: Query<Entity, With<CrashSummaryPanel>>,
+) {
+    if !bots.iter().any(|(_, crashes)| crashes.is_changed()) {
+        return;
+    }
+
+    let lines = crash_summary(
+        bots.iter()
+            .map(|(bot_id, crashes)| (bot_id, crashes.into_inner())),
+    );
+
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    if lines.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Px(400.0),
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(5.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.4, 0.1, 0.1, 0.9)),
+            CrashSummaryPanel,
+        ))
+        .with_children(|parent| {
+            let text_font = TextFont {
+                font_size: 12.0,
+                ..default()
+            };
+            for line in lines {
+                parent.spawn((
+                    Text::new(line),
+                    TextColor(Color::WHITE),
+                    text_font.clone(),
+                ));
+            }
+        });
+}
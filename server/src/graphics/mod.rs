@@ -3,9 +3,13 @@ use render_bots::{RenderBotsPlugin, RenderBotsSystemSet};
 use swarm_lib::Team;
 use tilemap::{TilemapPlugin, TilemapSystemSimUpdateSet};
 
+pub mod crash_summary;
 pub mod interaction;
+pub mod notifications;
 pub mod render_bots;
 pub mod tilemap;
+pub mod time_stats;
+pub mod timeline;
 
 pub struct GraphicsPlugin;
 
@@ -17,6 +21,10 @@ impl Plugin for GraphicsPlugin {
         app.add_plugins(TilemapPlugin);
         app.add_plugins(RenderBotsPlugin);
         app.add_plugins(interaction::InteractionPlugin);
+        app.add_plugins(notifications::NotificationsPlugin);
+        app.add_plugins(crash_summary::CrashSummaryPlugin);
+        app.add_plugins(time_stats::TimeStatsPlugin);
+        app.add_plugins(timeline::TimelinePlugin);
         app.insert_resource(MapMode::All);
         app.add_systems(Startup, load_tileset);
         app.configure_sets(
@@ -32,7 +40,7 @@ pub struct Textures {
     // ascii: Handle<Image>,
     terrain: Handle<Image>,
     items: Handle<Image>,
-    pawns: (Handle<Image>, Handle<TextureAtlasLayout>),
+    pub(crate) pawns: (Handle<Image>, Handle<TextureAtlasLayout>),
     fog_of_war: Handle<Image>,
 }
 
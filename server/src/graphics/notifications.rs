@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+
+use crate::game::bot_lib::BotLibReloaded;
+
+/// How long a notification stays on screen
+const NOTIFICATION_SECS: f32 = 4.0;
+
+pub struct NotificationsPlugin;
+
+impl Plugin for NotificationsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_notification_area)
+            .add_systems(Update, (show_bot_lib_reloaded, expire_notifications));
+    }
+}
+
+#[derive(Component)]
+pub struct NotificationArea;
+
+#[derive(Component)]
+struct Notification {
+    timer: Timer,
+}
+
+fn spawn_notification_area(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::End,
+            row_gap: Val::Px(5.0),
+            ..default()
+        },
+        NotificationArea,
+    ));
+}
+
+/// Show a short-lived message in the top right corner
+pub fn spawn_notification(
+    commands: &mut Commands,
+    area: Entity,
+    message: impl Into<String>,
+) {
+    commands.entity(area).with_children(|parent| {
+        parent
+            .spawn((
+                Node {
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.2, 0.2, 0.2, 0.9)),
+                Notification {
+                    timer: Timer::from_seconds(
+                        NOTIFICATION_SECS,
+                        TimerMode::Once,
+                    ),
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text::new(message),
+                    TextFont {
+                        font_size: 16.0,
+                        ..default()
+                    },
+                    TextColor(Color::WHITE),
+                ));
+            });
+    });
+}
+
+fn show_bot_lib_reloaded(
+    mut commands: Commands,
+    mut events: EventReader<BotLibReloaded>,
+    area: Query<Entity, With<NotificationArea>>,
+) {
+    let Ok(area) = area.get_single() else {
+        return;
+    };
+    for event in events.read() {
+        spawn_notification(
+            &mut commands,
+            area,
+            format!(
+                "Reloaded bot library (generation {}), {} bots re-created",
+                event.generation, event.bots_reloaded
+            ),
+        );
+    }
+}
+
+fn expire_notifications(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut notifications: Query<(Entity, &mut Notification)>,
+) {
+    for (entity, mut notification) in notifications.iter_mut() {
+        if notification.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -50,12 +50,14 @@ impl Plugin for TilemapPlugin {
             map_type: TilemapType::Square,
         });
         // Add system to update the resource
-        app.add_systems(OnEnter(GameState::InGame), setup_map);
-        app.add_systems(OnExit(GameState::InGame), remove_map);
+        for state in [GameState::InGame, GameState::Editing] {
+            app.add_systems(OnEnter(state.clone()), setup_map);
+            app.add_systems(OnExit(state), remove_map);
+        }
         app.add_systems(
             Update,
             (update_tilemap_world_coords, render_grid)
-                .run_if(in_state(GameState::InGame))
+                .run_if(GameState::shows_map)
                 .in_set(TilemapSystemSimUpdateSet),
         );
     }
@@ -324,6 +326,23 @@ impl TilemapWorldCoords {
         // Add the tilemap's translation to get world coordinates
         local_pos + self.transform.translation.xy()
     }
+
+    /// Converts world coordinates to the cell under them, or `None` if they
+    /// are off the map
+    pub fn world_to_pos(&self, world: Vec2, map_size: &MapSize) -> Option<Pos> {
+        let local_pos = world - self.transform.translation.xy();
+        let map_size = TilemapSize {
+            x: map_size.x,
+            y: map_size.y,
+        };
+        let tile_pos = TilePos::from_world_pos(
+            &local_pos,
+            &map_size,
+            &self.grid_size,
+            &self.map_type,
+        )?;
+        Some(Pos((tile_pos.x as usize, tile_pos.y as usize)))
+    }
 }
 
 /// System to update the TilemapWorldCoords resource with the latest component
@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use swarm_lib::{BotData, Team};
+
+use super::interaction::Selected;
+use crate::game::{
+    bot_update::BotId,
+    time_budget::{BotTimeStats, TeamTimeStats, TimeBudget},
+};
+
+pub struct TimeStatsPlugin;
+
+impl Plugin for TimeStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_time_stats_panel);
+    }
+}
+
+#[derive(Component)]
+struct TimeStatsPanel;
+
+/// Keeps a panel in the bottom right corner with the time each team has
+/// spent in `Bot::update`, and the time stats of the selected bot
+fn update_time_stats_panel(
+    mut commands: Commands,
+    selected: Res<Selected>,
+    team_time: Res<TeamTimeStats>,
+    time_budget: Res<TimeBudget>,
+    bots: Query<(&BotId, &BotData, Ref<BotTimeStats>)>,
+    panel: Query<Entity, With<TimeStatsPanel>>,
+) {
+    let selected_bot = match selected.as_ref() {
+        Selected::Bot(entity) => bots.get(*entity).ok(),
+        Selected::None => None,
+    };
+    let selected_changed = selected_bot
+        .as_ref()
+        .is_some_and(|(_, _, time_stats)| time_stats.is_changed());
+    if !selected.is_changed() && !team_time.is_changed() && !selected_changed {
+        return;
+    }
+
+    let mut lines = [Team::Player, Team::Enemy]
+        .into_iter()
+        .map(|team| format!("Team {team}: {:.1?}", team_time.get(team)))
+        .collect::<Vec<_>>();
+
+    if let Some((bot_id, bot_data, time_stats)) = selected_bot {
+        lines.push(format!("Bot {} ({})", bot_id.0, bot_data.team));
+        lines.push(format!(
+            "CPU last {:.1?}, avg {:.1?}, max {:.1?}",
+            time_stats.last,
+            time_stats.average(),
+            time_stats.max
+        ));
+        lines.push(format!("Wall last {:.1?}", time_stats.last_wall));
+        lines.push(format!(
+            "Total {:.1?} over {} updates",
+            time_stats.total, time_stats.updates
+        ));
+        lines.push(format!(
+            "Bank {:.1?} / {:.1?}, {} overruns{}",
+            time_stats.bank_remaining(&time_budget),
+            time_budget.bank,
+            time_stats.overruns,
+            if time_stats.penalized {
+                ", Noop next tick"
+            } else {
+                ""
+            }
+        ));
+    }
+
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Px(300.0),
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(5.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.15, 0.15, 0.15, 0.9)),
+            TimeStatsPanel,
+        ))
+        .with_children(|parent| {
+            let text_font = TextFont {
+                font_size: 12.0,
+                ..default()
+            };
+            for line in lines {
+                parent.spawn((
+                    Text::new(line),
+                    TextColor(Color::WHITE),
+                    text_font.clone(),
+                ));
+            }
+        });
+}
@@ -0,0 +1,171 @@
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use super::interaction::Selected;
+use crate::{
+    game::bot_update::BotId,
+    replay::{LiveOrReplay, ReplayEvent, ReplayTimeline},
+    types::Tick,
+};
+
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                spawn_timeline.run_if(not(any_with_component::<TimelineBar>)),
+                (scrub_timeline, jump_to_event, update_timeline).chain(),
+            )
+                .chain()
+                .run_if(in_state(LiveOrReplay::Replay)),
+        )
+        .add_systems(OnExit(LiveOrReplay::Replay), despawn_timeline);
+    }
+}
+
+/// The clickable bar along the bottom of the screen
+#[derive(Component)]
+struct TimelineBar;
+
+/// The filled part of the bar, up to the current tick
+#[derive(Component)]
+struct TimelineProgress;
+
+#[derive(Component)]
+struct TimelineLabel;
+
+fn spawn_timeline(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(40.0),
+                height: Val::Px(18.0),
+                position_type: PositionType::Absolute,
+                left: Val::Percent(30.0),
+                bottom: Val::Px(10.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.15, 0.15, 0.15, 0.9)),
+            Interaction::default(),
+            RelativeCursorPosition::default(),
+            TimelineBar,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Node {
+                    width: Val::Percent(0.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.4, 0.6, 0.9, 0.9)),
+                TimelineProgress,
+            ));
+            parent.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                Text::new(""),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                TimelineLabel,
+            ));
+        });
+}
+
+/// The timeline goes away once a live match is branched off the replay
+fn despawn_timeline(
+    mut commands: Commands,
+    bar: Query<Entity, With<TimelineBar>>,
+) {
+    for bar in bar.iter() {
+        commands.entity(bar).despawn_recursive();
+    }
+}
+
+/// Clicking or dragging on the bar seeks to that point of the replay
+fn scrub_timeline(
+    bar: Query<(&Interaction, &RelativeCursorPosition), With<TimelineBar>>,
+    timeline: Res<ReplayTimeline>,
+    mut tick: ResMut<Tick>,
+) {
+    for (interaction, cursor) in bar.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(cursor) = cursor.normalized else {
+            continue;
+        };
+        let target = timeline.tick_at(cursor.x);
+        if tick.0 != target {
+            tick.0 = target;
+        }
+    }
+}
+
+/// F jumps to the next failed action of the selected bot, B to the next bot
+/// spawn and V to the win. Home and End jump to the start and end.
+fn jump_to_event(
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selected>,
+    bot_ids: Query<&BotId>,
+    timeline: Res<ReplayTimeline>,
+    mut tick: ResMut<Tick>,
+) {
+    let selected_bot = match selected.as_ref() {
+        Selected::Bot(entity) => bot_ids.get(*entity).ok().copied(),
+        Selected::None => None,
+    };
+
+    let target = if keys.just_pressed(KeyCode::KeyF) {
+        let Some(selected_bot) = selected_bot else {
+            info!("Select a bot to jump to its next failed action");
+            return;
+        };
+        timeline.next_event(tick.0, |event| {
+            *event == ReplayEvent::ActionFailed(selected_bot)
+        })
+    } else if keys.just_pressed(KeyCode::KeyB) {
+        timeline.next_event(tick.0, |event| {
+            matches!(event, ReplayEvent::BotSpawned(_))
+        })
+    } else if keys.just_pressed(KeyCode::KeyV) {
+        timeline.next_event(timeline.first_tick.saturating_sub(1), |event| {
+            matches!(event, ReplayEvent::Won(_))
+        })
+    } else if keys.just_pressed(KeyCode::Home) {
+        Some(timeline.first_tick)
+    } else if keys.just_pressed(KeyCode::End) {
+        Some(timeline.last_tick)
+    } else {
+        return;
+    };
+
+    match target {
+        Some(target) => tick.0 = target,
+        None => info!("No such event after tick {}", tick.0),
+    }
+}
+
+fn update_timeline(
+    tick: Res<Tick>,
+    timeline: Res<ReplayTimeline>,
+    mut progress: Query<&mut Node, With<TimelineProgress>>,
+    mut label: Query<&mut Text, With<TimelineLabel>>,
+    spawned: Query<(), Added<TimelineLabel>>,
+) {
+    if !tick.is_changed() && spawned.is_empty() {
+        return;
+    }
+    for mut node in progress.iter_mut() {
+        node.width = Val::Percent(timeline.fraction(tick.0) * 100.0);
+    }
+    for mut text in label.iter_mut() {
+        text.0 = format!("Tick {} / {}", tick.0, timeline.last_tick);
+    }
+}
@@ -0,0 +1,115 @@
+use argh::FromArgs;
+use bevy::prelude::*;
+use eyre::bail;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use swarm_lib::CellKind;
+
+use super::{
+    terrain::{add_border, keep_largest_area, populate},
+    Level,
+};
+use crate::{
+    graphics::tilemap::MapSize,
+    types::{CellState, GridWorld, Seed},
+};
+
+pub struct Caves;
+
+#[derive(FromArgs, Debug, Clone)]
+/// Winding caves grown with a cellular automaton
+pub struct CavesArgs {
+    #[argh(option, default = "60")]
+    /// the width of the map
+    pub width: usize,
+    #[argh(option, default = "40")]
+    /// the height of the map
+    pub height: usize,
+    #[argh(option, default = "45")]
+    /// the percentage of cells that start as rock. Higher makes narrower
+    /// caves
+    pub fill_percent: u32,
+    #[argh(option, default = "5")]
+    /// the number of smoothing passes. More makes rounder caves
+    pub smoothing: usize,
+    #[argh(option, default = "40")]
+    /// the number of Metal to scatter
+    pub metal: usize,
+}
+
+impl Level for Caves {
+    const NAME: &'static str = "caves";
+    const DESCRIPTION: &'static str =
+        "Winding caves grown with a cellular automaton";
+    type Args = CavesArgs;
+
+    fn init(
+        args: &CavesArgs,
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()> {
+        let (width, height) = (args.width, args.height);
+        if width < 8 || height < 8 {
+            bail!("The map must be at least 8 by 8");
+        }
+        if args.fill_percent > 100 {
+            bail!("--fill-percent must be at most 100");
+        }
+        commands.insert_resource(MapSize {
+            x: width as u32,
+            y: height as u32,
+        });
+
+        let mut rng = SmallRng::seed_from_u64(seed.0);
+        let mut grid_world = GridWorld::new(width, height, CellState::empty());
+        for x in 0..width {
+            for y in 0..height {
+                if rng.random_range(0..100) < args.fill_percent {
+                    grid_world.set_tuple(x, y, CellState::blocked());
+                }
+            }
+        }
+        add_border(&mut grid_world);
+
+        for _ in 0..args.smoothing {
+            grid_world = smooth(&grid_world);
+        }
+        if keep_largest_area(&mut grid_world) == 0 {
+            bail!("Every cell is rock, try a lower --fill-percent");
+        }
+
+        populate(&mut grid_world, commands, &mut rng, args.metal);
+        commands.insert_resource(grid_world);
+        Ok(())
+    }
+
+    fn map_size(args: &CavesArgs) -> Option<(usize, usize)> {
+        Some((args.width, args.height))
+    }
+}
+
+/// One step of the automaton. A cell becomes rock when most of the 8 cells
+/// around it are rock, and opens up when most are open. The edge stays rock.
+fn smooth(grid_world: &GridWorld) -> GridWorld {
+    let (width, height) = (grid_world.width(), grid_world.height());
+    let mut next = grid_world.clone();
+    for x in 1..width - 1 {
+        for y in 1..height - 1 {
+            let mut rock = 0;
+            for nx in x - 1..=x + 1 {
+                for ny in y - 1..=y + 1 {
+                    let is_rock =
+                        grid_world.get_tuple(nx, ny).kind == CellKind::Blocked;
+                    if (nx, ny) != (x, y) && is_rock {
+                        rock += 1;
+                    }
+                }
+            }
+            if rock > 4 {
+                next.set_tuple(x, y, CellState::blocked());
+            } else if rock < 4 {
+                next.set_tuple(x, y, CellState::empty());
+            }
+        }
+    }
+    next
+}
@@ -1,7 +1,6 @@
 use argh::FromArgs;
 use bevy::prelude::*;
-use rand::Rng;
-use serde::{Deserialize, Serialize};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 use swarm_lib::{
     known_map::{ClientCellState, KnownMap},
     BotData,
@@ -15,20 +14,16 @@ use swarm_lib::{
     Team,
 };
 
-use super::Levels;
+use super::Level;
 use crate::{
     graphics::tilemap::MapSize,
-    types::{CellState, GridWorld},
+    types::{CellState, GridWorld, Seed},
 };
 
-#[derive(
-    FromArgs, Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq,
-)]
-#[argh(
-    subcommand,
-    name = "econ-loop",
-    description = "Scenario to test economic loop"
-)]
+pub struct EconLoop;
+
+#[derive(FromArgs, Debug, Clone)]
+/// Scenario to test economic loop
 pub struct EconLoopArgs {
     #[argh(option, default = "100")]
     /// the width of the map
@@ -38,12 +33,26 @@ pub struct EconLoopArgs {
     pub height: usize,
 }
 
-pub(super) fn init_econ_loop(mut commands: Commands, level_args: Res<Levels>) {
-    let args = match &*level_args {
-        Levels::EconLoop(args) => args,
-        _ => panic!("Expected EconLoop level"),
-    };
+impl Level for EconLoop {
+    const NAME: &'static str = "econ-loop";
+    const DESCRIPTION: &'static str = "Scenario to test economic loop";
+    type Args = EconLoopArgs;
+
+    fn init(
+        args: &EconLoopArgs,
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()> {
+        init_econ_loop(args, commands, seed);
+        Ok(())
+    }
+
+    fn map_size(args: &EconLoopArgs) -> Option<(usize, usize)> {
+        Some((args.width, args.height))
+    }
+}
 
+fn init_econ_loop(args: &EconLoopArgs, commands: &mut Commands, seed: Seed) {
     let (width, height) = (args.width, args.height);
     commands.insert_resource(MapSize {
         x: width as u32,
@@ -51,7 +60,7 @@ pub(super) fn init_econ_loop(mut commands: Commands, level_args: Res<Levels>) {
     });
 
     let mut grid_world = GridWorld::new(width, height, CellState::empty());
-    let mut rng = rand::rng();
+    let mut rng = SmallRng::seed_from_u64(seed.0);
 
     // Add a border of Blocked cells around the edge of the grid
     for x in 0..width {
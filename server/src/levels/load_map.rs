@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use argh::FromArgs;
+use bevy::prelude::*;
+use eyre::{bail, WrapErr};
+use serde::{Deserialize, Serialize};
+use swarm_lib::{
+    known_map::{ClientCellState, KnownMap},
+    BotData,
+    CellKind,
+    Energy,
+    FrameKind,
+    Item,
+    MatchInfo,
+    Pos,
+    Region,
+    RegionKind,
+    Subsystem,
+    Subsystems,
+    Team,
+};
+
+use super::{terrain::add_border, Level};
+use crate::{
+    graphics::tilemap::MapSize,
+    types::{CellState, GridWorld, Seed},
+    WinCondition,
+};
+
+pub struct LoadMap;
+
+#[derive(FromArgs, Debug, Clone)]
+/// A hand-authored map from a TOML file
+pub struct LoadMapArgs {
+    #[argh(positional)]
+    /// the map file
+    pub file: String,
+}
+
+/// A hand-authored level, for example:
+///
+/// ```toml
+/// # Rows from the top of the map down. `#` is blocked, `.` is empty, and
+/// # `c`, `f`, `t` and `m` are a Crumb, Fent, Truffle or Metal on the ground
+/// terrain = """
+/// #######
+/// #.t.f.#
+/// #.....#
+/// #######
+/// """
+///
+/// [[spawn]]
+/// pos = [1, 1]
+/// team = "Player"
+/// frame = "Flea"
+/// subsystems = { CargoBay = 1 }
+/// inventory = { Truffle = 1 }
+///
+/// # Named parts of the map the bots can look up
+/// [[region]]
+/// name = "truffle patch"
+/// kind = "Zone"
+/// from = [1, 2]
+/// to = [3, 2]
+///
+/// [win]
+/// hold = { Fent = 1, Truffle = 2 }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MapFile {
+    pub terrain: String,
+    #[serde(default)]
+    pub spawn: Vec<SpawnPoint>,
+    #[serde(default)]
+    pub region: Vec<MapRegion>,
+    #[serde(default)]
+    pub win: WinCondition,
+}
+
+/// A named part of the map, shared with the bots through `MatchInfo`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MapRegion {
+    pub name: String,
+    pub kind: RegionKind,
+    /// A corner, in the same coordinates as spawn positions
+    pub from: (usize, usize),
+    /// The opposite corner, inclusive. Just `from` if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<(usize, usize)>,
+}
+
+impl MapRegion {
+    pub fn region(&self) -> Region {
+        Region::new(
+            self.name.clone(),
+            self.kind,
+            Pos(self.from),
+            Pos(self.to.unwrap_or(self.from)),
+        )
+    }
+}
+
+/// A bot the map starts with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnPoint {
+    /// x and y, with y pointing up like in the window
+    pub pos: (usize, usize),
+    pub team: Team,
+    #[serde(default)]
+    pub frame: FrameKind,
+    #[serde(default)]
+    pub subsystems: HashMap<Subsystem, u8>,
+    #[serde(default)]
+    pub inventory: HashMap<Item, u8>,
+    /// Full if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub energy: Option<u32>,
+}
+
+impl MapFile {
+    /// Reads a map file and checks that it describes a map that can be built
+    pub fn load(path: &str) -> eyre::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Could not read {path}"))?;
+        let map: MapFile = toml::from_str(&text)?;
+        map.check()?;
+        Ok(map)
+    }
+
+    /// Checks the map and writes it to `path`
+    pub fn save(&self, path: &str) -> eyre::Result<()> {
+        self.check()?;
+        let text = toml::to_string(self)?;
+        std::fs::write(path, text)
+            .wrap_err_with(|| format!("Could not write {path}"))
+    }
+
+    /// The terrain and items of `grid_world` with the bots in `spawn`
+    pub fn new(
+        grid_world: &GridWorld,
+        spawn: Vec<SpawnPoint>,
+        win: WinCondition,
+    ) -> Self {
+        let (width, height) = (grid_world.width(), grid_world.height());
+        let mut terrain = String::new();
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let cell = grid_world.get_tuple(x, y);
+                terrain.push(match (cell.kind, cell.item) {
+                    (CellKind::Blocked, _) => '#',
+                    (_, Some(Item::Crumb)) => 'c',
+                    (_, Some(Item::Fent)) => 'f',
+                    (_, Some(Item::Truffle)) => 't',
+                    (_, Some(Item::Metal)) => 'm',
+                    (_, None) => '.',
+                });
+            }
+            terrain.push('\n');
+        }
+        MapFile {
+            terrain,
+            spawn,
+            region: Vec::new(),
+            win,
+        }
+    }
+
+    /// An empty map with a wall around the edge and no bots
+    pub fn blank(width: usize, height: usize) -> eyre::Result<Self> {
+        if width < 3 || height < 3 {
+            bail!("The map must be at least 3 by 3");
+        }
+        let mut grid_world = GridWorld::new(width, height, CellState::empty());
+        add_border(&mut grid_world);
+        Ok(MapFile::new(
+            &grid_world,
+            Vec::new(),
+            WinCondition::default(),
+        ))
+    }
+
+    /// Checks that the map can be built
+    pub fn check(&self) -> eyre::Result<()> {
+        if self.win.hold.is_empty() {
+            bail!("The win condition needs at least one item to hold");
+        }
+        let grid_world = self.grid_world()?;
+        for spawn in &self.spawn {
+            self.check_spawn(&grid_world, spawn)?;
+        }
+        for region in &self.region {
+            self.check_region(&grid_world, region)?;
+        }
+        Ok(())
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &str> {
+        self.terrain
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows().next().map_or(0, |row| row.chars().count())
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows().count()
+    }
+
+    /// Inserts the `GridWorld`, `MapSize` and `MatchInfo` and spawns the bots
+    pub fn build(&self, commands: &mut Commands) {
+        let (width, height) = (self.width(), self.height());
+        commands.insert_resource(MapSize {
+            x: width as u32,
+            y: height as u32,
+        });
+
+        commands.insert_resource(MatchInfo {
+            regions: self.region.iter().map(MapRegion::region).collect(),
+        });
+
+        let mut grid_world = self.grid_world().expect("Checked when loaded");
+        for spawn in &self.spawn {
+            let bot = commands.spawn(spawn.bot_data(width, height)).id();
+            grid_world.get_mut(Pos(spawn.pos)).pawn = Some(bot);
+        }
+
+        commands.insert_resource(grid_world);
+    }
+
+    /// The terrain and items, without the bots
+    pub fn grid_world(&self) -> eyre::Result<GridWorld> {
+        let (width, height) = (self.width(), self.height());
+        if width == 0 {
+            bail!("The map has no terrain");
+        }
+        let mut grid_world = GridWorld::new(width, height, CellState::empty());
+        for (row_idx, row) in self.rows().enumerate() {
+            if row.chars().count() != width {
+                bail!(
+                    "Row {} of the terrain is {} cells wide instead of {width}",
+                    row_idx + 1,
+                    row.chars().count()
+                );
+            }
+            // The first row is the top of the map
+            let y = height - 1 - row_idx;
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    '#' => CellState::blocked(),
+                    '.' => CellState::empty(),
+                    'c' | 'f' | 't' | 'm' => CellState {
+                        item: Some(match c {
+                            'c' => Item::Crumb,
+                            'f' => Item::Fent,
+                            't' => Item::Truffle,
+                            _ => Item::Metal,
+                        }),
+                        ..CellState::empty()
+                    },
+                    _ => bail!(
+                        "Unknown terrain {c:?} at row {}, column {}",
+                        row_idx + 1,
+                        x + 1
+                    ),
+                };
+                grid_world.set_tuple(x, y, cell);
+            }
+        }
+        Ok(grid_world)
+    }
+
+    fn check_spawn(
+        &self,
+        grid_world: &GridWorld,
+        spawn: &SpawnPoint,
+    ) -> eyre::Result<()> {
+        let Some(cell) = grid_world.try_get(Pos(spawn.pos)) else {
+            bail!("Spawn at {:?} is outside the map", spawn.pos);
+        };
+        if !cell.can_enter() {
+            bail!("Spawn at {:?} is on a blocked cell", spawn.pos);
+        }
+        let others = self.spawn.iter().filter(|other| other.pos == spawn.pos);
+        if others.count() > 1 {
+            bail!("More than one bot spawns at {:?}", spawn.pos);
+        }
+        spawn.check_loadout()
+    }
+
+    fn check_region(
+        &self,
+        grid_world: &GridWorld,
+        region: &MapRegion,
+    ) -> eyre::Result<()> {
+        if region.name.is_empty() {
+            bail!("A region at {:?} has no name", region.from);
+        }
+        for corner in [Some(region.from), region.to].into_iter().flatten() {
+            if grid_world.try_get(Pos(corner)).is_none() {
+                bail!(
+                    "Region {:?} has a corner at {corner:?}, outside the map",
+                    region.name
+                );
+            }
+        }
+        let others =
+            self.region.iter().filter(|other| other.name == region.name);
+        if others.count() > 1 {
+            bail!("More than one region is named {:?}", region.name);
+        }
+        Ok(())
+    }
+}
+
+impl SpawnPoint {
+    fn subsystems(&self) -> Subsystems {
+        Subsystems::new(self.subsystems.iter().map(|(s, count)| (*s, *count)))
+    }
+
+    /// Checks that the subsystems fit the frame and the items fit the cargo
+    /// bays
+    pub fn check_loadout(&self) -> eyre::Result<()> {
+        let subsystems = self.subsystems();
+        if subsystems.size() > self.frame.slots() {
+            bail!(
+                "Spawn at {:?} has subsystems needing {} slots but a {} has {}",
+                self.pos,
+                subsystems.size(),
+                self.frame,
+                self.frame.slots()
+            );
+        }
+        let items = self.inventory.values().map(|&count| count as u32);
+        let capacity = subsystems.get(Subsystem::CargoBay) as u32;
+        if items.sum::<u32>() > capacity {
+            bail!(
+                "Spawn at {:?} starts with more items than its {capacity} \
+                 cargo bays hold",
+                self.pos
+            );
+        }
+        Ok(())
+    }
+
+    pub fn bot_data(&self, width: usize, height: usize) -> BotData {
+        let mut bot_data = BotData::new(
+            self.frame,
+            self.subsystems(),
+            Pos(self.pos),
+            self.team,
+            Energy(0),
+            KnownMap::new(width, height, ClientCellState::default()),
+            Vec::new(),
+        );
+        for (item, count) in &self.inventory {
+            bot_data.inventory.add(*item, *count);
+        }
+        bot_data.energy = match self.energy {
+            Some(energy) => Energy(energy),
+            None => bot_data.max_energy(),
+        };
+        bot_data
+    }
+}
+
+impl Level for LoadMap {
+    const NAME: &'static str = "load-map";
+    const DESCRIPTION: &'static str = "A hand-authored map from a TOML file";
+    type Args = LoadMapArgs;
+
+    fn init(
+        args: &LoadMapArgs,
+        commands: &mut Commands,
+        _seed: Seed,
+    ) -> eyre::Result<()> {
+        MapFile::load(&args.file)?.build(commands);
+        Ok(())
+    }
+
+    fn win_condition(args: &LoadMapArgs) -> eyre::Result<WinCondition> {
+        Ok(MapFile::load(&args.file)?.win)
+    }
+
+    fn map_size(args: &LoadMapArgs) -> Option<(usize, usize)> {
+        let map = MapFile::load(&args.file).ok()?;
+        Some((map.width(), map.height()))
+    }
+}
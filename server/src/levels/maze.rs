@@ -0,0 +1,128 @@
+use argh::FromArgs;
+use bevy::prelude::*;
+use eyre::bail;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use swarm_lib::CellKind;
+
+use super::{terrain::populate, Level};
+use crate::{
+    graphics::tilemap::MapSize,
+    types::{CellState, GridWorld, Seed},
+};
+
+pub struct Maze;
+
+#[derive(FromArgs, Debug, Clone)]
+/// A maze of corridors one cell wide
+pub struct MazeArgs {
+    #[argh(option, default = "41")]
+    /// the width of the map. Best odd, or the right edge is two cells thick
+    pub width: usize,
+    #[argh(option, default = "31")]
+    /// the height of the map. Best odd, or the top edge is two cells thick
+    pub height: usize,
+    #[argh(option, default = "10")]
+    /// the percentage of walls between corridors to knock down afterwards.
+    /// 0 makes a perfect maze with one way between any two cells
+    pub loops_percent: u32,
+    #[argh(option, default = "20")]
+    /// the number of Metal to scatter
+    pub metal: usize,
+}
+
+impl Level for Maze {
+    const NAME: &'static str = "maze";
+    const DESCRIPTION: &'static str = "A maze of corridors one cell wide";
+    type Args = MazeArgs;
+
+    fn init(
+        args: &MazeArgs,
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()> {
+        let (width, height) = (args.width, args.height);
+        if width < 5 || height < 5 {
+            bail!("The map must be at least 5 by 5");
+        }
+        commands.insert_resource(MapSize {
+            x: width as u32,
+            y: height as u32,
+        });
+
+        let mut rng = SmallRng::seed_from_u64(seed.0);
+        let mut grid_world =
+            GridWorld::new(width, height, CellState::blocked());
+        carve_maze(&mut grid_world, &mut rng);
+        knock_down_walls(&mut grid_world, &mut rng, args.loops_percent);
+
+        populate(&mut grid_world, commands, &mut rng, args.metal);
+        commands.insert_resource(grid_world);
+        Ok(())
+    }
+
+    fn map_size(args: &MazeArgs) -> Option<(usize, usize)> {
+        Some((args.width, args.height))
+    }
+}
+
+/// Carves corridors through the cells with odd coordinates with a randomised
+/// depth first search, so that every corridor is connected without loops
+fn carve_maze(grid_world: &mut GridWorld, rng: &mut SmallRng) {
+    // The last odd coordinate that still has the edge after it
+    let last_odd = |len: usize| if len % 2 == 0 { len - 3 } else { len - 2 };
+    let (max_x, max_y) =
+        (last_odd(grid_world.width()), last_odd(grid_world.height()));
+
+    grid_world.set_tuple(1, 1, CellState::empty());
+    let mut stack = vec![(1, 1)];
+    while let Some(&(x, y)) = stack.last() {
+        let unvisited = [
+            (x + 2 <= max_x).then(|| (x + 2, y)),
+            (y + 2 <= max_y).then(|| (x, y + 2)),
+            (x >= 3).then(|| (x - 2, y)),
+            (y >= 3).then(|| (x, y - 2)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|&(nx, ny)| {
+            grid_world.get_tuple(nx, ny).kind == CellKind::Blocked
+        })
+        .collect::<Vec<_>>();
+
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (nx, ny) = unvisited[rng.random_range(0..unvisited.len())];
+        // Open the wall between the two cells as well
+        grid_world.set_tuple((x + nx) / 2, (y + ny) / 2, CellState::empty());
+        grid_world.set_tuple(nx, ny, CellState::empty());
+        stack.push((nx, ny));
+    }
+}
+
+/// Opens walls that separate two corridors, so that there is more than one
+/// way around
+fn knock_down_walls(
+    grid_world: &mut GridWorld,
+    rng: &mut SmallRng,
+    loops_percent: u32,
+) {
+    let (width, height) = (grid_world.width(), grid_world.height());
+    let is_open =
+        |grid: &GridWorld, x, y| grid.get_tuple(x, y).kind == CellKind::Empty;
+    for x in 1..width - 1 {
+        for y in 1..height - 1 {
+            if is_open(grid_world, x, y) {
+                continue;
+            }
+            let between_corridors = (is_open(grid_world, x - 1, y)
+                && is_open(grid_world, x + 1, y))
+                || (is_open(grid_world, x, y - 1)
+                    && is_open(grid_world, x, y + 1));
+            if between_corridors && rng.random_range(0..100) < loops_percent {
+                grid_world.set_tuple(x, y, CellState::empty());
+            }
+        }
+    }
+}
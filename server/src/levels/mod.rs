@@ -1,95 +1,271 @@
-use argh::{FromArgValue, FromArgs};
-use bevy::{prelude::*, state::state::FreelyMutableState};
-use econ_loop::EconLoopArgs;
-use random_crumbs_and_truffles::{
-    init_random_crumbs_and_truffles,
-    RandomCrumbsAndTrufflesArgs,
-};
+use std::{marker::PhantomData, sync::Arc};
+
+use argh::{EarlyExit, FromArgs};
+use bevy::{ecs::world::CommandQueue, prelude::*};
+use caves::Caves;
+use econ_loop::EconLoop;
+use eyre::{bail, eyre};
+pub use load_map::{LoadMap, MapFile, MapRegion, SpawnPoint};
+use maze::Maze;
+use random_crumbs_and_truffles::RandomCrumbsAndTruffles;
+use rooms::Rooms;
 use serde::{Deserialize, Serialize};
-use small_crumbs_and_truffles::{
-    init_small_crumbs_and_truffles,
-    SmallCrumbsAndTrufflesArgs,
+use small_crumbs_and_truffles::SmallCrumbsAndTruffles;
+use symmetric::Symmetric;
+pub use validate::{
+    check_level,
+    find_valid_seed,
+    ItemDistances,
+    MapConstraints,
+    MapReport,
+    TeamDistances,
 };
 
-use crate::GameState;
+use crate::{types::Seed, GameState, WinCondition};
 
+mod caves;
 mod econ_loop;
+mod load_map;
+mod maze;
 mod random_crumbs_and_truffles;
+mod rooms;
 mod small_crumbs_and_truffles;
+mod symmetric;
+mod terrain;
+mod validate;
 
-pub struct LevelsPlugin;
+/// Builds the level picked on the command line when the app starts
+pub struct LevelsPlugin {
+    pub levels: LevelRegistry,
+    /// `None` when only looking at a replay
+    pub level: Option<LevelArgs>,
+}
 
 impl Plugin for LevelsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            OnEnter(LevelsDiscriminants::SmallCrumbsAndTruffles),
-            (init_small_crumbs_and_truffles, transition_to_in_game),
-        )
-        .add_systems(
-            OnEnter(LevelsDiscriminants::RandomCrumbsAndTruffles),
-            (init_random_crumbs_and_truffles, transition_to_in_game),
-        )
-        .add_systems(
-            OnEnter(LevelsDiscriminants::EconLoop),
-            (econ_loop::init_econ_loop, transition_to_in_game),
+        if let Some(level) = &self.level {
+            app.insert_resource(level.clone());
+        }
+        app.insert_resource(self.levels.clone()).add_systems(
+            Startup,
+            (init_level, validate::report_level, transition_to_in_game)
+                .chain()
+                .run_if(resource_exists::<LevelArgs>),
         );
     }
 }
 
+/// A level that can be picked by name on the command line once it is
+/// registered with `LevelRegistry::register`
+pub trait Level: Send + Sync + 'static {
+    /// The name it's picked by, such as `econ-loop`
+    const NAME: &'static str;
+    /// Shown in the list of levels
+    const DESCRIPTION: &'static str;
+    /// Parsed from the arguments after the name
+    type Args: FromArgs;
+
+    /// Inserts the `GridWorld` and `MapSize` and spawns the starting bots.
+    /// May insert a `MatchInfo` to name parts of the map.
+    fn init(
+        args: &Self::Args,
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()>;
+
+    fn win_condition(_args: &Self::Args) -> eyre::Result<WinCondition> {
+        Ok(WinCondition::default())
+    }
+
+    /// Width and height of the map in cells, to open a window that fits it
+    fn map_size(_args: &Self::Args) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+/// The level a match is played on, by name and with the arguments it was
+/// picked with so that it can be built again from a replay
 #[derive(
-    Debug,
-    Clone,
-    Serialize,
-    Deserialize,
-    Hash,
-    Eq,
-    PartialEq,
-    strum_macros::EnumDiscriminants,
-    FromArgs,
-    Resource,
+    Resource, Debug, Clone, Default, Serialize, Deserialize, Hash, Eq, PartialEq,
 )]
-#[argh(subcommand)]
-#[strum_discriminants(derive(Hash))]
-pub enum Levels {
-    Replay(ReplayArgs),
-    SmallCrumbsAndTruffles(SmallCrumbsAndTrufflesArgs),
-    RandomCrumbsAndTruffles(RandomCrumbsAndTrufflesArgs),
-    EconLoop(EconLoopArgs),
+pub struct LevelArgs {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl LevelArgs {
+    /// The level name followed by its arguments, or `None` if empty
+    pub fn from_command_line(words: &[String]) -> Option<Self> {
+        let (name, args) = words.split_first()?;
+        Some(LevelArgs {
+            name: name.clone(),
+            args: args.to_vec(),
+        })
+    }
 }
 
-impl Default for Levels {
+/// Every level that can be picked, by name
+#[derive(Resource, Clone)]
+pub struct LevelRegistry {
+    levels: Vec<Arc<dyn RegisteredLevel>>,
+}
+
+impl Default for LevelRegistry {
+    /// The levels built into the server
     fn default() -> Self {
-        Self::Replay(ReplayArgs {})
+        let mut levels = LevelRegistry { levels: Vec::new() };
+        levels
+            .register::<SmallCrumbsAndTruffles>()
+            .register::<RandomCrumbsAndTruffles>()
+            .register::<EconLoop>()
+            .register::<Symmetric>()
+            .register::<Caves>()
+            .register::<Rooms>()
+            .register::<Maze>()
+            .register::<LoadMap>();
+        levels
+    }
+}
+
+impl LevelRegistry {
+    /// Makes `L` available by its name, replacing any level already
+    /// registered under it
+    pub fn register<L: Level>(&mut self) -> &mut Self {
+        self.levels.retain(|level| level.name() != L::NAME);
+        self.levels.push(Arc::new(Registered::<L>(PhantomData)));
+        self
+    }
+
+    fn get(&self, name: &str) -> eyre::Result<&dyn RegisteredLevel> {
+        match self.levels.iter().find(|level| level.name() == name) {
+            Some(level) => Ok(level.as_ref()),
+            None => bail!("Unknown level {name}. Levels are:\n{}", self.list()),
+        }
+    }
+
+    /// One line per level with its name and description
+    pub fn list(&self) -> String {
+        let lines = self.levels.iter().map(|level| {
+            format!("  {:<28}{}", level.name(), level.description())
+        });
+        lines.collect::<Vec<_>>().join("\n")
+    }
+
+    /// Parses the arguments of a level without building it, so that mistakes
+    /// are reported before the window opens. `--help` is an `EarlyExit` with
+    /// a successful status.
+    pub fn check(&self, level: &LevelArgs) -> Result<(), EarlyExit> {
+        let registered = self.get(&level.name).map_err(|err| EarlyExit {
+            output: format!("{err:#}"),
+            status: Err(()),
+        })?;
+        registered.check(&level.args)
+    }
+
+    pub fn win_condition(
+        &self,
+        level: &LevelArgs,
+    ) -> eyre::Result<WinCondition> {
+        self.get(&level.name)?.win_condition(&level.args)
+    }
+
+    pub fn map_size(&self, level: &LevelArgs) -> Option<(usize, usize)> {
+        self.get(&level.name).ok()?.map_size(&level.args)
+    }
+
+    /// Builds the level into a world of its own, without the rest of the
+    /// app, to look at the map it makes
+    pub fn build_detached(
+        &self,
+        level: &LevelArgs,
+        seed: Seed,
+    ) -> eyre::Result<World> {
+        let registered = self.get(&level.name)?;
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        registered.init(&level.args, &mut commands, seed)?;
+        queue.apply(&mut world);
+        Ok(world)
     }
 }
 
-impl States for LevelsDiscriminants {
-    const DEPENDENCY_DEPTH: usize = 1;
+/// A `Level` with its arguments still unparsed, so that levels with
+/// different argument types can be kept together
+trait RegisteredLevel: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn check(&self, args: &[String]) -> Result<(), EarlyExit>;
+    fn init(
+        &self,
+        args: &[String],
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()>;
+    fn win_condition(&self, args: &[String]) -> eyre::Result<WinCondition>;
+    fn map_size(&self, args: &[String]) -> Option<(usize, usize)>;
 }
 
-impl FreelyMutableState for LevelsDiscriminants {}
-
-impl FromArgValue for LevelsDiscriminants {
-    fn from_arg_value(value: &str) -> Result<Self, String> {
-        Ok(match value {
-            "small-crumbs-and-truffles" => {
-                LevelsDiscriminants::SmallCrumbsAndTruffles
-            }
-            "random-crumbs-and-truffles" => {
-                LevelsDiscriminants::RandomCrumbsAndTruffles
-            }
-            "econ-loop" => LevelsDiscriminants::EconLoop,
-            _ => return Err(format!("Invalid level: {}", value)),
-        })
+struct Registered<L>(PhantomData<fn() -> L>);
+
+impl<L: Level> Registered<L> {
+    fn parse(args: &[String]) -> Result<L::Args, EarlyExit> {
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        L::Args::from_args(&[L::NAME], &args)
+    }
+
+    fn try_parse(args: &[String]) -> eyre::Result<L::Args> {
+        Self::parse(args).map_err(|exit| eyre!("{}", exit.output))
+    }
+}
+
+impl<L: Level> RegisteredLevel for Registered<L> {
+    fn name(&self) -> &'static str {
+        L::NAME
+    }
+
+    fn description(&self) -> &'static str {
+        L::DESCRIPTION
+    }
+
+    fn check(&self, args: &[String]) -> Result<(), EarlyExit> {
+        Self::parse(args).map(|_| ())
+    }
+
+    fn init(
+        &self,
+        args: &[String],
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()> {
+        L::init(&Self::try_parse(args)?, commands, seed)
+    }
+
+    fn win_condition(&self, args: &[String]) -> eyre::Result<WinCondition> {
+        L::win_condition(&Self::try_parse(args)?)
+    }
+
+    fn map_size(&self, args: &[String]) -> Option<(usize, usize)> {
+        L::map_size(&Self::parse(args).ok()?)
+    }
+}
+
+fn init_level(
+    mut commands: Commands,
+    levels: Res<LevelRegistry>,
+    level: Res<LevelArgs>,
+    seed: Res<Seed>,
+) {
+    let result = levels.get(&level.name).and_then(|registered| {
+        // Before the bots are spawned, which are told the rules when created
+        commands.insert_resource(registered.win_condition(&level.args)?);
+        registered.init(&level.args, &mut commands, *seed)
+    });
+    if let Err(err) = result {
+        panic!("Could not build level {}: {err:#}", level.name);
     }
 }
 
 fn transition_to_in_game(mut next_state: ResMut<NextState<GameState>>) {
     next_state.set(GameState::InGame);
 }
-
-#[derive(
-    FromArgs, Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq,
-)]
-#[argh(subcommand, name = "replay", description = "Replay a game")]
-pub struct ReplayArgs {}
@@ -1,7 +1,6 @@
 use argh::FromArgs;
 use bevy::prelude::*;
-use rand::Rng;
-use serde::{Deserialize, Serialize};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 use swarm_lib::{
     known_map::{ClientCellState, KnownMap},
     BotData,
@@ -14,20 +13,16 @@ use swarm_lib::{
     Team,
 };
 
-use super::Levels;
+use super::Level;
 use crate::{
     graphics::tilemap::MapSize,
-    types::{CellState, GridWorld},
+    types::{CellState, GridWorld, Seed},
 };
 
-#[derive(
-    FromArgs, Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq,
-)]
-#[argh(
-    subcommand,
-    name = "random-crumbs-and-truffles",
-    description = "A random map with crumbs and truffles"
-)]
+pub struct RandomCrumbsAndTruffles;
+
+#[derive(FromArgs, Debug, Clone)]
+/// A random map with crumbs and truffles
 pub struct RandomCrumbsAndTrufflesArgs {
     #[argh(option, default = "20")]
     /// the width of the map
@@ -37,15 +32,30 @@ pub struct RandomCrumbsAndTrufflesArgs {
     pub height: usize,
 }
 
-pub(super) fn init_random_crumbs_and_truffles(
-    mut commands: Commands,
-    level_args: Res<Levels>,
-) {
-    let args = match &*level_args {
-        Levels::RandomCrumbsAndTruffles(args) => args,
-        _ => panic!("Expected RandomCrumbsAndTruffles level"),
-    };
+impl Level for RandomCrumbsAndTruffles {
+    const NAME: &'static str = "random-crumbs-and-truffles";
+    const DESCRIPTION: &'static str = "A random map with crumbs and truffles";
+    type Args = RandomCrumbsAndTrufflesArgs;
+
+    fn init(
+        args: &RandomCrumbsAndTrufflesArgs,
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()> {
+        init_random_crumbs_and_truffles(args, commands, seed);
+        Ok(())
+    }
 
+    fn map_size(args: &RandomCrumbsAndTrufflesArgs) -> Option<(usize, usize)> {
+        Some((args.width, args.height))
+    }
+}
+
+fn init_random_crumbs_and_truffles(
+    args: &RandomCrumbsAndTrufflesArgs,
+    commands: &mut Commands,
+    seed: Seed,
+) {
     let (width, height) = (args.width, args.height);
     commands.insert_resource(MapSize {
         x: width as u32,
@@ -53,7 +63,7 @@ pub(super) fn init_random_crumbs_and_truffles(
     });
 
     let mut grid_world = GridWorld::new(width, height, CellState::empty());
-    let mut rng = rand::rng();
+    let mut rng = SmallRng::seed_from_u64(seed.0);
 
     // Add a border of Blocked cells around the edge of the grid
     for x in 0..width {
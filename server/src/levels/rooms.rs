@@ -0,0 +1,185 @@
+use argh::FromArgs;
+use bevy::prelude::*;
+use eyre::bail;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use super::{
+    terrain::{keep_largest_area, populate},
+    Level,
+};
+use crate::{
+    graphics::tilemap::MapSize,
+    types::{CellState, GridWorld, Seed},
+};
+
+pub struct Rooms;
+
+#[derive(FromArgs, Debug, Clone)]
+/// Rooms joined by corridors, laid out by splitting the map in two over and
+/// over
+pub struct RoomsArgs {
+    #[argh(option, default = "60")]
+    /// the width of the map
+    pub width: usize,
+    #[argh(option, default = "40")]
+    /// the height of the map
+    pub height: usize,
+    #[argh(option, default = "4")]
+    /// the number of times the map is split. There are up to 2 to the power
+    /// of this many rooms
+    pub splits: u32,
+    #[argh(option, default = "4")]
+    /// the smallest width and height of a room
+    pub min_room: usize,
+    #[argh(option, default = "30")]
+    /// the number of Metal to scatter
+    pub metal: usize,
+}
+
+impl Level for Rooms {
+    const NAME: &'static str = "rooms";
+    const DESCRIPTION: &'static str = "Rooms joined by corridors";
+    type Args = RoomsArgs;
+
+    fn init(
+        args: &RoomsArgs,
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()> {
+        let (width, height) = (args.width, args.height);
+        if args.min_room < 2 {
+            bail!("--min-room must be at least 2");
+        }
+        if width < args.min_room + 3 || height < args.min_room + 3 {
+            bail!("The map is too small for a room of {}", args.min_room);
+        }
+        commands.insert_resource(MapSize {
+            x: width as u32,
+            y: height as u32,
+        });
+
+        let mut rng = SmallRng::seed_from_u64(seed.0);
+        let mut grid_world =
+            GridWorld::new(width, height, CellState::blocked());
+        let whole_map = Area {
+            x: 1,
+            y: 1,
+            width: width - 2,
+            height: height - 2,
+        };
+        split(&mut grid_world, &mut rng, args, whole_map, args.splits);
+        keep_largest_area(&mut grid_world);
+
+        populate(&mut grid_world, commands, &mut rng, args.metal);
+        commands.insert_resource(grid_world);
+        Ok(())
+    }
+
+    fn map_size(args: &RoomsArgs) -> Option<(usize, usize)> {
+        Some((args.width, args.height))
+    }
+}
+
+/// Part of the map being split, by its bottom left cell and size
+#[derive(Debug, Clone, Copy)]
+struct Area {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Carves rooms into `area` and the corridors joining them, and returns the
+/// center of one of the rooms for the caller to join to its other half
+fn split(
+    grid_world: &mut GridWorld,
+    rng: &mut SmallRng,
+    args: &RoomsArgs,
+    area: Area,
+    splits_left: u32,
+) -> (usize, usize) {
+    // Each half needs room for the smallest room and a wall next to it
+    let min_half = args.min_room + 1;
+    let can_split_x = area.width >= 2 * min_half;
+    let can_split_y = area.height >= 2 * min_half;
+    if splits_left == 0 || !(can_split_x || can_split_y) {
+        return carve_room(grid_world, rng, args, area);
+    }
+
+    // Split across the longer side, so rooms don't get too thin
+    let split_x = can_split_x && (!can_split_y || area.width >= area.height);
+    let (a, b) = if split_x {
+        let at = rng.random_range(min_half..=area.width - min_half);
+        (
+            Area { width: at, ..area },
+            Area {
+                x: area.x + at,
+                width: area.width - at,
+                ..area
+            },
+        )
+    } else {
+        let at = rng.random_range(min_half..=area.height - min_half);
+        (
+            Area { height: at, ..area },
+            Area {
+                y: area.y + at,
+                height: area.height - at,
+                ..area
+            },
+        )
+    };
+
+    let center_a = split(grid_world, rng, args, a, splits_left - 1);
+    let center_b = split(grid_world, rng, args, b, splits_left - 1);
+    carve_corridor(grid_world, rng, center_a, center_b);
+    if rng.random_bool(0.5) {
+        center_a
+    } else {
+        center_b
+    }
+}
+
+/// Carves a room somewhere in `area`, leaving a wall on its top and right so
+/// that it doesn't merge with the rooms next to it, and returns its center
+fn carve_room(
+    grid_world: &mut GridWorld,
+    rng: &mut SmallRng,
+    args: &RoomsArgs,
+    area: Area,
+) -> (usize, usize) {
+    // `split` keeps every area at least `min_room + 1` across
+    let (max_width, max_height) = (area.width - 1, area.height - 1);
+    let width = rng.random_range(args.min_room..=max_width);
+    let height = rng.random_range(args.min_room..=max_height);
+    let x = area.x + rng.random_range(0..=max_width - width);
+    let y = area.y + rng.random_range(0..=max_height - height);
+    for room_x in x..x + width {
+        for room_y in y..y + height {
+            grid_world.set_tuple(room_x, room_y, CellState::empty());
+        }
+    }
+    (x + width / 2, y + height / 2)
+}
+
+/// Carves an L-shaped corridor between two cells, turning at one of the two
+/// corners
+fn carve_corridor(
+    grid_world: &mut GridWorld,
+    rng: &mut SmallRng,
+    (from_x, from_y): (usize, usize),
+    (to_x, to_y): (usize, usize),
+) {
+    let corner = if rng.random_bool(0.5) {
+        (to_x, from_y)
+    } else {
+        (from_x, to_y)
+    };
+    for (a, b) in [((from_x, from_y), corner), (corner, (to_x, to_y))] {
+        for x in a.0.min(b.0)..=a.0.max(b.0) {
+            for y in a.1.min(b.1)..=a.1.max(b.1) {
+                grid_world.set_tuple(x, y, CellState::empty());
+            }
+        }
+    }
+}
@@ -1,6 +1,5 @@
 use argh::FromArgs;
 use bevy::prelude::*;
-use serde::{Deserialize, Serialize};
 use swarm_lib::{
     known_map::{ClientCellState, KnownMap},
     BotData,
@@ -13,22 +12,38 @@ use swarm_lib::{
     Team,
 };
 
+use super::Level;
 use crate::{
     graphics::tilemap::MapSize,
-    types::{CellState, GridWorld},
+    types::{CellState, GridWorld, Seed},
 };
 
-#[derive(
-    FromArgs, Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq,
-)]
-#[argh(
-    subcommand,
-    name = "small-crumbs-and-truffles",
-    description = "A small map with crumbs and truffles"
-)]
+pub struct SmallCrumbsAndTruffles;
+
+#[derive(FromArgs, Debug, Clone)]
+/// A small map with crumbs and truffles
 pub struct SmallCrumbsAndTrufflesArgs {}
 
-pub(super) fn init_small_crumbs_and_truffles(mut commands: Commands) {
+impl Level for SmallCrumbsAndTruffles {
+    const NAME: &'static str = "small-crumbs-and-truffles";
+    const DESCRIPTION: &'static str = "A small map with crumbs and truffles";
+    type Args = SmallCrumbsAndTrufflesArgs;
+
+    fn init(
+        _args: &SmallCrumbsAndTrufflesArgs,
+        commands: &mut Commands,
+        _seed: Seed,
+    ) -> eyre::Result<()> {
+        init_small_crumbs_and_truffles(commands);
+        Ok(())
+    }
+
+    fn map_size(_args: &SmallCrumbsAndTrufflesArgs) -> Option<(usize, usize)> {
+        Some((20, 20))
+    }
+}
+
+fn init_small_crumbs_and_truffles(commands: &mut Commands) {
     let (width, height) = (20, 20);
     commands.insert_resource(MapSize {
         x: width as u32,
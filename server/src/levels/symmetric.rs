@@ -0,0 +1,418 @@
+use argh::{FromArgValue, FromArgs};
+use bevy::prelude::*;
+use eyre::bail;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use swarm_lib::{
+    known_map::{ClientCellState, KnownMap},
+    BotData,
+    BuildingKind,
+    Energy,
+    FrameKind,
+    Item,
+    MatchInfo,
+    Pos,
+    Region,
+    RegionKind,
+    Subsystem,
+    Subsystems,
+    Team,
+};
+
+use super::{
+    terrain::{add_border, reachable_from},
+    Level,
+};
+use crate::{
+    graphics::tilemap::MapSize,
+    types::{CellState, GridWorld, Seed},
+};
+
+/// Maps are generated again with the next random numbers until the spawns
+/// are connected, up to this many times
+const MAX_ATTEMPTS: usize = 100;
+
+pub struct Symmetric;
+
+#[derive(FromArgs, Debug, Clone)]
+/// A random map where both teams start with the same surroundings
+pub struct SymmetricArgs {
+    #[argh(option, default = "40")]
+    /// the width of the map
+    pub width: usize,
+    #[argh(option, default = "30")]
+    /// the height of the map
+    pub height: usize,
+    #[argh(option, default = "Symmetry::Point")]
+    /// how the enemy half copies the player half: mirror (left to right) or
+    /// point (rotated half a turn)
+    pub symmetry: Symmetry,
+    #[argh(option, default = "6")]
+    /// the number of wall segments in each half
+    pub walls: usize,
+    #[argh(option, default = "4")]
+    /// the number of metal clusters in each half
+    pub metal_clusters: usize,
+    #[argh(option, default = "1")]
+    /// the number of Fents in each half
+    pub fents: usize,
+    #[argh(option, default = "2")]
+    /// the number of Truffles in each half
+    pub truffles: usize,
+}
+
+/// How a cell in one half maps to its copy in the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Flipped left to right
+    Mirror,
+    /// Rotated half a turn around the center
+    Point,
+}
+
+impl FromArgValue for Symmetry {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        match value {
+            "mirror" => Ok(Symmetry::Mirror),
+            "point" => Ok(Symmetry::Point),
+            _ => Err(format!(
+                "Invalid symmetry: {value}. Expected mirror or point"
+            )),
+        }
+    }
+}
+
+impl Symmetry {
+    fn copy_of(
+        self,
+        (x, y): (usize, usize),
+        width: usize,
+        height: usize,
+    ) -> (usize, usize) {
+        match self {
+            Symmetry::Mirror => (width - 1 - x, y),
+            Symmetry::Point => (width - 1 - x, height - 1 - y),
+        }
+    }
+}
+
+impl Level for Symmetric {
+    const NAME: &'static str = "symmetric";
+    const DESCRIPTION: &'static str =
+        "A random map where both teams start with the same surroundings";
+    type Args = SymmetricArgs;
+
+    fn init(
+        args: &SymmetricArgs,
+        commands: &mut Commands,
+        seed: Seed,
+    ) -> eyre::Result<()> {
+        let (mut grid_world, spawns) = generate(args, seed)?;
+        let (width, height) = (args.width, args.height);
+        commands.insert_resource(MapSize {
+            x: width as u32,
+            y: height as u32,
+        });
+        commands.insert_resource(MatchInfo {
+            regions: spawns
+                .iter()
+                .map(|&(pos, team)| {
+                    let name = match team {
+                        Team::Player => "player base",
+                        Team::Enemy => "enemy base",
+                    };
+                    Region::new(
+                        name,
+                        RegionKind::Spawn(team),
+                        Pos(pos),
+                        Pos(pos),
+                    )
+                })
+                .collect(),
+        });
+
+        for (pos, team) in spawns {
+            let mut bot_data = BotData::new(
+                FrameKind::Building(BuildingKind::Small),
+                Subsystems::new([
+                    (Subsystem::Assembler, 1),
+                    (Subsystem::CargoBay, 3),
+                    (Subsystem::PowerCell, 2),
+                ]),
+                Pos(pos),
+                team,
+                Energy(0),
+                KnownMap::new(width, height, ClientCellState::default()),
+                Vec::new(),
+            );
+            let capacity = bot_data.inventory.capacity;
+            bot_data.inventory.add(Item::Metal, capacity);
+            bot_data.energy = bot_data.max_energy();
+
+            let bot = commands.spawn(bot_data).id();
+            grid_world.get_tuple_mut(pos.0, pos.1).pawn = Some(bot);
+        }
+
+        commands.insert_resource(grid_world);
+        Ok(())
+    }
+
+    fn map_size(args: &SymmetricArgs) -> Option<(usize, usize)> {
+        Some((args.width, args.height))
+    }
+}
+
+type Spawns = [((usize, usize), Team); 2];
+
+/// Builds the terrain and items and picks a spawn for each team. Every open
+/// cell can be reached from both spawns.
+fn generate(
+    args: &SymmetricArgs,
+    seed: Seed,
+) -> eyre::Result<(GridWorld, Spawns)> {
+    if args.width < 10 || args.height < 8 {
+        bail!("The map must be at least 10 wide and 8 high");
+    }
+    let mut rng = SmallRng::seed_from_u64(seed.0);
+    for _ in 0..MAX_ATTEMPTS {
+        if let Some(map) = try_generate(args, &mut rng) {
+            return Ok(map);
+        }
+    }
+    bail!(
+        "Could not connect the spawns in {MAX_ATTEMPTS} tries, try fewer \
+         walls or a bigger map"
+    );
+}
+
+fn try_generate(
+    args: &SymmetricArgs,
+    rng: &mut SmallRng,
+) -> Option<(GridWorld, Spawns)> {
+    let (width, height) = (args.width, args.height);
+    let copy_of = |pos| args.symmetry.copy_of(pos, width, height);
+    let mut grid_world = GridWorld::new(width, height, CellState::empty());
+    add_border(&mut grid_world);
+
+    // The player spawns in the left half, so its copy is in the right half
+    let player_spawn = (
+        rng.random_range(2..width / 2 - 1),
+        rng.random_range(2..height - 2),
+    );
+    let enemy_spawn = copy_of(player_spawn);
+    let near_spawn = |(x, y): (usize, usize)| {
+        [player_spawn, enemy_spawn]
+            .iter()
+            .any(|spawn| spawn.0.abs_diff(x) <= 1 && spawn.1.abs_diff(y) <= 1)
+    };
+
+    for _ in 0..args.walls {
+        let segment_length = rng.random_range(3..=width.max(height) / 3);
+        let start_x = rng.random_range(1..width - 1);
+        let start_y = rng.random_range(1..height - 1);
+        // 0=right, 1=up, 2=left, 3=down
+        let direction = rng.random_range(0..4);
+        for i in 0..segment_length {
+            let (x, y) = match direction {
+                0 => (start_x + i, start_y),
+                1 => (start_x, start_y + i),
+                2 => (start_x.wrapping_sub(i), start_y),
+                _ => (start_x, start_y.wrapping_sub(i)),
+            };
+            if x >= width - 1 || y >= height - 1 || near_spawn((x, y)) {
+                continue;
+            }
+            let (copy_x, copy_y) = copy_of((x, y));
+            grid_world.set_tuple(x, y, CellState::blocked());
+            grid_world.set_tuple(copy_x, copy_y, CellState::blocked());
+        }
+    }
+
+    let reachable = reachable_from(&grid_world, [Pos(player_spawn)]);
+    if !reachable[enemy_spawn] {
+        return None;
+    }
+    // Seal off pockets no bot can get to. The reachable cells are the same
+    // in both halves, since they are connected to both spawns.
+    for x in 0..width {
+        for y in 0..height {
+            if !reachable[(x, y)] {
+                grid_world.set_tuple(x, y, CellState::blocked());
+            }
+        }
+    }
+
+    // Picks a free cell whose copy is a different free cell
+    let free_pair = |grid_world: &GridWorld, rng: &mut SmallRng| {
+        (0..1000).find_map(|_| {
+            let pos = (
+                rng.random_range(1..width - 1),
+                rng.random_range(1..height - 1),
+            );
+            let copy = copy_of(pos);
+            let is_free = |(x, y): (usize, usize)| {
+                let cell = grid_world.get_tuple(x, y);
+                cell.can_enter() && cell.item.is_none() && !near_spawn((x, y))
+            };
+            (pos != copy && is_free(pos) && is_free(copy)).then_some(pos)
+        })
+    };
+
+    for _ in 0..args.metal_clusters {
+        let center = free_pair(&grid_world, rng)?;
+        let cluster_size = rng.random_range(2..=4);
+        let cells = grid_world
+            .nearby(Pos(center), 1)
+            .map(|(pos, _)| pos.0)
+            .take(cluster_size)
+            .collect::<Vec<_>>();
+        for (x, y) in cells {
+            let (copy_x, copy_y) = copy_of((x, y));
+            let cell = grid_world.get_tuple(x, y);
+            let copy = grid_world.get_tuple(copy_x, copy_y);
+            if (x, y) == (copy_x, copy_y)
+                || !cell.can_enter()
+                || !copy.can_enter()
+                || near_spawn((x, y))
+            {
+                continue;
+            }
+            grid_world.get_tuple_mut(x, y).item = Some(Item::Metal);
+            grid_world.get_tuple_mut(copy_x, copy_y).item = Some(Item::Metal);
+        }
+    }
+
+    let items = std::iter::repeat_n(Item::Fent, args.fents)
+        .chain(std::iter::repeat_n(Item::Truffle, args.truffles));
+    for item in items {
+        let (x, y) = free_pair(&grid_world, rng)?;
+        let (copy_x, copy_y) = copy_of((x, y));
+        grid_world.get_tuple_mut(x, y).item = Some(item);
+        grid_world.get_tuple_mut(copy_x, copy_y).item = Some(item);
+    }
+
+    let spawns = [(player_spawn, Team::Player), (enemy_spawn, Team::Enemy)];
+    Some((grid_world, spawns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(symmetry: Symmetry, width: usize, height: usize) -> SymmetricArgs {
+        SymmetricArgs {
+            width,
+            height,
+            symmetry,
+            walls: 6,
+            metal_clusters: 4,
+            fents: 1,
+            truffles: 2,
+        }
+    }
+
+    /// Panics unless every cell matches its copy and the spawns can reach
+    /// each other and every item
+    fn check_map(
+        (grid_world, spawns): &(GridWorld, Spawns),
+        symmetry: Symmetry,
+    ) {
+        let (width, height) = (grid_world.width(), grid_world.height());
+        for x in 0..width {
+            for y in 0..height {
+                let (copy_x, copy_y) = symmetry.copy_of((x, y), width, height);
+                let cell = grid_world.get_tuple(x, y);
+                let copy = grid_world.get_tuple(copy_x, copy_y);
+                assert_eq!(
+                    (cell.kind, cell.item),
+                    (copy.kind, copy.item),
+                    "{symmetry:?} copy of {:?} at {:?}",
+                    (x, y),
+                    (copy_x, copy_y)
+                );
+            }
+        }
+
+        let [(player_spawn, Team::Player), (enemy_spawn, Team::Enemy)] =
+            *spawns
+        else {
+            panic!("Expected a player and an enemy spawn, got {spawns:?}");
+        };
+        assert_eq!(symmetry.copy_of(player_spawn, width, height), enemy_spawn);
+        for (spawn, other) in
+            [(player_spawn, enemy_spawn), (enemy_spawn, player_spawn)]
+        {
+            let reachable = reachable_from(grid_world, [Pos(spawn)]);
+            assert!(reachable[other], "{other:?} from {spawn:?}");
+            for x in 0..width {
+                for y in 0..height {
+                    if grid_world.get_tuple(x, y).item.is_some() {
+                        assert!(
+                            reachable[(x, y)],
+                            "{:?} from {spawn:?}",
+                            (x, y)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn halves_match_and_are_connected() {
+        for symmetry in [Symmetry::Mirror, Symmetry::Point] {
+            for seed in 0..20 {
+                let map = generate(&args(symmetry, 40, 30), Seed(seed))
+                    .unwrap_or_else(|err| {
+                        panic!("{symmetry:?} with seed {seed}: {err:#}")
+                    });
+                check_map(&map, symmetry);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_makes_the_same_map() {
+        let cells = |grid_world: &GridWorld| {
+            grid_world
+                .iter()
+                .map(|(pos, cell)| (pos, cell.kind, cell.item))
+                .collect::<Vec<_>>()
+        };
+        for symmetry in [Symmetry::Mirror, Symmetry::Point] {
+            let args = args(symmetry, 40, 30);
+            let (a, a_spawns) = generate(&args, Seed(7)).unwrap();
+            let (b, b_spawns) = generate(&args, Seed(7)).unwrap();
+            assert_eq!(cells(&a), cells(&b), "{symmetry:?}");
+            assert_eq!(a_spawns, b_spawns, "{symmetry:?}");
+        }
+    }
+
+    #[test]
+    fn smallest_maps_build_or_say_why_not() {
+        for symmetry in [Symmetry::Mirror, Symmetry::Point] {
+            for seed in 0..20 {
+                // The defaults may not fit, but must say so
+                match generate(&args(symmetry, 10, 8), Seed(seed)) {
+                    Ok(map) => check_map(&map, symmetry),
+                    Err(err) => assert!(
+                        err.to_string().contains("bigger map"),
+                        "{symmetry:?} with seed {seed}: {err:#}"
+                    ),
+                }
+
+                let sparse = SymmetricArgs {
+                    walls: 0,
+                    metal_clusters: 1,
+                    ..args(symmetry, 10, 8)
+                };
+                let map = generate(&sparse, Seed(seed)).unwrap_or_else(|err| {
+                    panic!("{symmetry:?} with seed {seed}: {err:#}")
+                });
+                check_map(&map, symmetry);
+            }
+
+            let err = generate(&args(symmetry, 9, 8), Seed(0)).unwrap_err();
+            assert!(err.to_string().contains("at least 10 wide"), "{err:#}");
+        }
+    }
+}
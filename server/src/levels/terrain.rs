@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+
+use array2d::Array2D;
+use bevy::prelude::*;
+use rand::{rngs::SmallRng, Rng};
+use swarm_lib::{
+    known_map::{ClientCellState, KnownMap},
+    BotData,
+    BuildingKind,
+    CellKind,
+    Energy,
+    FrameKind,
+    Item,
+    MatchInfo,
+    Pos,
+    Region,
+    RegionKind,
+    Subsystem,
+    Subsystems,
+    Team,
+};
+
+use crate::types::{CellState, GridWorld};
+
+/// Blocks the cells around the edge of the grid
+pub(super) fn add_border(grid_world: &mut GridWorld) {
+    let (width, height) = (grid_world.width(), grid_world.height());
+    for x in 0..width {
+        grid_world.set_tuple(x, 0, CellState::blocked());
+        grid_world.set_tuple(x, height - 1, CellState::blocked());
+    }
+    for y in 0..height {
+        grid_world.set_tuple(0, y, CellState::blocked());
+        grid_world.set_tuple(width - 1, y, CellState::blocked());
+    }
+}
+
+/// The cells a bot could walk to from any of `starts`, ignoring other bots.
+/// Indexed by x then y like the grid.
+pub(super) fn reachable_from(
+    grid_world: &GridWorld,
+    starts: impl IntoIterator<Item = Pos>,
+) -> Array2D<bool> {
+    let distances = distances_from(grid_world, starts);
+    let (width, height) = (grid_world.width(), grid_world.height());
+    Array2D::from_iter_row_major(
+        distances.elements_row_major_iter().map(Option::is_some),
+        width,
+        height,
+    )
+    .expect("Same size as the grid")
+}
+
+/// The number of moves from the nearest of `starts` to each cell, or `None`
+/// for cells that can't be reached. Other bots are ignored.
+pub(super) fn distances_from(
+    grid_world: &GridWorld,
+    starts: impl IntoIterator<Item = Pos>,
+) -> Array2D<Option<usize>> {
+    let (width, height) = (grid_world.width(), grid_world.height());
+    let mut distances = Array2D::filled_with(None, width, height);
+    let mut queue = VecDeque::new();
+    for start in starts {
+        if grid_world.in_bounds(&start) {
+            distances[(start.x(), start.y())] = Some(0);
+            queue.push_back((start.x(), start.y()));
+        }
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+        let distance = distances[(x, y)].expect("Queued cells are reached");
+        let neighbours = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        for (nx, ny) in neighbours {
+            let Some(cell) = grid_world.grid.get(nx, ny) else {
+                continue;
+            };
+            if cell.kind == CellKind::Blocked || distances[(nx, ny)].is_some() {
+                continue;
+            }
+            distances[(nx, ny)] = Some(distance + 1);
+            queue.push_back((nx, ny));
+        }
+    }
+    distances
+}
+
+/// Blocks every open cell outside the largest connected open area, so that
+/// nothing is placed where the bots can't get to. Returns the number of open
+/// cells left.
+pub(super) fn keep_largest_area(grid_world: &mut GridWorld) -> usize {
+    let (width, height) = (grid_world.width(), grid_world.height());
+    let mut seen = Array2D::filled_with(false, width, height);
+    let mut largest: Option<(usize, Array2D<bool>)> = None;
+    for (pos, cell) in grid_world.iter() {
+        if cell.kind == CellKind::Blocked || seen[pos] {
+            continue;
+        }
+        let area = reachable_from(grid_world, [Pos(pos)]);
+        let mut size = 0;
+        for (cell_pos, in_area) in area.enumerate_row_major() {
+            if *in_area {
+                seen[cell_pos] = true;
+                size += 1;
+            }
+        }
+        if largest.as_ref().is_none_or(|(largest, _)| size > *largest) {
+            largest = Some((size, area));
+        }
+    }
+
+    let Some((size, area)) = largest else {
+        return 0;
+    };
+    for x in 0..width {
+        for y in 0..height {
+            if !area[(x, y)] {
+                grid_world.set_tuple(x, y, CellState::blocked());
+            }
+        }
+    }
+    size
+}
+
+/// Spawns a player base and scatters `metal` Metal, a Fent and 2 Truffles
+/// over the open cells
+pub(super) fn populate(
+    grid_world: &mut GridWorld,
+    commands: &mut Commands,
+    rng: &mut SmallRng,
+    metal: usize,
+) {
+    let (width, height) = (grid_world.width(), grid_world.height());
+    let mut free_cells = grid_world
+        .iter()
+        .filter(|(_, cell)| cell.can_enter() && cell.item.is_none())
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    let mut take_free_cell = |rng: &mut SmallRng| {
+        (!free_cells.is_empty()).then(|| {
+            free_cells.swap_remove(rng.random_range(0..free_cells.len()))
+        })
+    };
+
+    if let Some((x, y)) = take_free_cell(rng) {
+        let mut bot_data = BotData::new(
+            FrameKind::Building(BuildingKind::Small),
+            Subsystems::new([
+                (Subsystem::Assembler, 1),
+                (Subsystem::CargoBay, 3),
+                (Subsystem::PowerCell, 2),
+            ]),
+            Pos((x, y)),
+            Team::Player,
+            Energy(0),
+            KnownMap::new(width, height, ClientCellState::default()),
+            Vec::new(),
+        );
+        let capacity = bot_data.inventory.capacity;
+        bot_data.inventory.add(Item::Metal, capacity);
+        bot_data.energy = bot_data.max_energy();
+
+        commands.insert_resource(MatchInfo {
+            regions: vec![Region::new(
+                "player base",
+                RegionKind::Spawn(Team::Player),
+                Pos((x, y)),
+                Pos((x, y)),
+            )],
+        });
+        let bot = commands.spawn(bot_data).id();
+        grid_world.get_tuple_mut(x, y).pawn = Some(bot);
+    }
+
+    let items = [Item::Fent, Item::Truffle, Item::Truffle]
+        .into_iter()
+        .chain(std::iter::repeat_n(Item::Metal, metal));
+    for item in items {
+        let Some((x, y)) = take_free_cell(rng) else {
+            break;
+        };
+        grid_world.get_tuple_mut(x, y).item = Some(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        levels::{LevelArgs, LevelRegistry, MapReport},
+        types::Seed,
+    };
+
+    fn build(words: &[&str], seed: u64) -> eyre::Result<World> {
+        let level = LevelArgs {
+            name: words[0].to_string(),
+            args: words[1..].iter().map(|word| word.to_string()).collect(),
+        };
+        LevelRegistry::default().build_detached(&level, Seed(seed))
+    }
+
+    #[test]
+    fn keeps_only_the_largest_area() {
+        let mut grid_world = GridWorld::new(8, 3, CellState::blocked());
+        for x in [1, 2, 4, 5, 6] {
+            grid_world.set_tuple(x, 1, CellState::empty());
+        }
+        assert_eq!(keep_largest_area(&mut grid_world), 3);
+        assert_eq!(grid_world.get_tuple(1, 1).kind, CellKind::Blocked);
+        assert_eq!(grid_world.get_tuple(2, 1).kind, CellKind::Blocked);
+        assert_eq!(grid_world.get_tuple(5, 1).kind, CellKind::Empty);
+    }
+
+    #[test]
+    fn generated_maps_can_be_walked_from_the_base() {
+        for level in ["caves", "rooms", "maze"] {
+            for seed in 0..10 {
+                let mut world = build(&[level], seed).unwrap();
+                let report = MapReport::of_world(&mut world);
+                assert!(
+                    report.unreachable_areas.is_empty()
+                        && report.unreachable_items.is_empty(),
+                    "{level} with seed {seed}:\n{report}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_makes_the_same_map() {
+        let cells = |world: &World| {
+            world
+                .resource::<GridWorld>()
+                .iter()
+                .map(|(pos, cell)| (pos, cell.kind, cell.item))
+                .collect::<Vec<_>>()
+        };
+        for level in ["caves", "rooms", "maze"] {
+            let a = build(&[level], 7).unwrap();
+            let b = build(&[level], 7).unwrap();
+            assert_eq!(cells(&a), cells(&b), "{level}");
+            assert_eq!(
+                a.resource::<MatchInfo>(),
+                b.resource::<MatchInfo>(),
+                "{level}"
+            );
+        }
+    }
+
+    #[test]
+    fn smallest_maps_build() {
+        for seed in 0..50 {
+            build(&["rooms", "--width", "7", "--height", "7"], seed).unwrap();
+            build(
+                &["rooms", "--width", "5", "--height", "5", "--min-room", "2"],
+                seed,
+            )
+            .unwrap();
+            build(&["maze", "--width", "5", "--height", "5"], seed).unwrap();
+            // Too much rock is an error rather than a panic
+            let _ = build(&["caves", "--width", "8", "--height", "8"], seed);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt;
+
+use array2d::Array2D;
+use bevy::prelude::*;
+use eyre::bail;
+use strum::VariantArray;
+use swarm_lib::{BotData, CellKind, Item, Pos, Team};
+
+use super::{
+    terrain::{distances_from, reachable_from},
+    LevelArgs,
+    LevelRegistry,
+};
+use crate::types::{GridWorld, Seed};
+
+/// Conditions a generated map has to meet. A map that doesn't is generated
+/// again with the next seed.
+#[derive(Resource, Debug, Clone)]
+pub struct MapConstraints {
+    /// Every item can be reached from a spawn
+    pub all_reachable: bool,
+    /// The most the teams' mean distances to an item kind may differ by
+    pub max_distance_gap: Option<f32>,
+    pub max_attempts: u64,
+}
+
+impl MapConstraints {
+    pub fn is_empty(&self) -> bool {
+        !self.all_reachable && self.max_distance_gap.is_none()
+    }
+
+    /// Why `report` doesn't meet the constraints. Empty if it does.
+    pub fn violations(&self, report: &MapReport) -> Vec<String> {
+        let mut violations = Vec::new();
+        if self.all_reachable && !report.unreachable_items.is_empty() {
+            violations.push(format!(
+                "{} items can't be reached",
+                report.unreachable_items.len()
+            ));
+        }
+        if let Some(max_gap) = self.max_distance_gap {
+            for item in Item::VARIANTS {
+                let means = report.teams.iter().filter_map(|team| {
+                    Some((team.team, team.item(*item)?.mean?))
+                });
+                let (Some(closest), Some(furthest)) = (
+                    means.clone().min_by(|a, b| a.1.total_cmp(&b.1)),
+                    means.max_by(|a, b| a.1.total_cmp(&b.1)),
+                ) else {
+                    continue;
+                };
+                let gap = furthest.1 - closest.1;
+                if gap > max_gap {
+                    violations.push(format!(
+                        "Team {} is on average {gap:.1} cells further from \
+                         {item:?} than team {}",
+                        furthest.0, closest.0
+                    ));
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// What can be reached from the spawns of a map, and how far away it is
+#[derive(Debug, Clone, Default)]
+pub struct MapReport {
+    /// Items that no spawn can reach
+    pub unreachable_items: Vec<(Pos, Item)>,
+    /// The size and one cell of each open area no spawn can reach
+    pub unreachable_areas: Vec<(usize, Pos)>,
+    pub teams: Vec<TeamDistances>,
+}
+
+/// How far the spawns of one team are from each item kind
+#[derive(Debug, Clone)]
+pub struct TeamDistances {
+    pub team: Team,
+    pub items: Vec<ItemDistances>,
+}
+
+/// Distances in moves from the nearest spawn of a team to the items of one
+/// kind it can reach
+#[derive(Debug, Clone)]
+pub struct ItemDistances {
+    pub item: Item,
+    pub reachable: usize,
+    pub nearest: Option<usize>,
+    pub mean: Option<f32>,
+}
+
+impl TeamDistances {
+    fn item(&self, item: Item) -> Option<&ItemDistances> {
+        self.items.iter().find(|distances| distances.item == item)
+    }
+}
+
+impl MapReport {
+    /// Flood fills the map from `spawns`
+    pub fn new(
+        grid_world: &GridWorld,
+        spawns: impl IntoIterator<Item = (Pos, Team)>,
+    ) -> Self {
+        let spawns = spawns.into_iter().collect::<Vec<_>>();
+        let reachable =
+            reachable_from(grid_world, spawns.iter().map(|(pos, _)| *pos));
+
+        let mut report = MapReport::default();
+        let (width, height) = (grid_world.width(), grid_world.height());
+        let mut seen = Array2D::filled_with(false, width, height);
+        for ((x, y), cell) in grid_world.iter() {
+            if cell.kind == CellKind::Blocked || reachable[(x, y)] {
+                continue;
+            }
+            if let Some(item) = cell.item {
+                report.unreachable_items.push((Pos((x, y)), item));
+            }
+            if seen[(x, y)] {
+                continue;
+            }
+            let area = reachable_from(grid_world, [Pos((x, y))]);
+            let mut size = 0;
+            for (pos, in_area) in area.enumerate_row_major() {
+                if *in_area {
+                    seen[pos] = true;
+                    size += 1;
+                }
+            }
+            report.unreachable_areas.push((size, Pos((x, y))));
+        }
+        report
+            .unreachable_areas
+            .sort_by_key(|(size, _)| usize::MAX - size);
+
+        for team in [Team::Player, Team::Enemy] {
+            let team_spawns = spawns
+                .iter()
+                .filter(|(_, spawn_team)| *spawn_team == team)
+                .map(|(pos, _)| *pos)
+                .collect::<Vec<_>>();
+            if team_spawns.is_empty() {
+                continue;
+            }
+            let distances = distances_from(grid_world, team_spawns);
+            let items = Item::VARIANTS.iter().map(|item| {
+                let item_distances = grid_world
+                    .iter()
+                    .filter(|(_, cell)| cell.item == Some(*item))
+                    .filter_map(|(pos, _)| distances[pos])
+                    .collect::<Vec<_>>();
+                let count = item_distances.len();
+                ItemDistances {
+                    item: *item,
+                    reachable: count,
+                    nearest: item_distances.iter().min().copied(),
+                    mean: (count > 0).then(|| {
+                        item_distances.iter().sum::<usize>() as f32
+                            / count as f32
+                    }),
+                }
+            });
+            report.teams.push(TeamDistances {
+                team,
+                items: items.collect(),
+            });
+        }
+        report
+    }
+
+    /// Flood fills a level built by `LevelRegistry::build_detached`
+    pub fn of_world(world: &mut World) -> Self {
+        let spawns = world
+            .query::<&BotData>()
+            .iter(world)
+            .map(|bot_data| (bot_data.pos, bot_data.team))
+            .collect::<Vec<_>>();
+        MapReport::new(world.resource::<GridWorld>(), spawns)
+    }
+}
+
+impl fmt::Display for MapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.unreachable_items.is_empty() {
+            writeln!(f, "Every item can be reached from a spawn")?;
+        }
+        for (pos, item) in &self.unreachable_items {
+            writeln!(f, "{item:?} at {:?} can't be reached", pos.0)?;
+        }
+        for (size, pos) in &self.unreachable_areas {
+            writeln!(
+                f,
+                "An area of {size} cells around {:?} can't be reached",
+                pos.0
+            )?;
+        }
+        for team in &self.teams {
+            writeln!(f, "Team {}:", team.team)?;
+            for distances in &team.items {
+                let (Some(nearest), Some(mean)) =
+                    (distances.nearest, distances.mean)
+                else {
+                    writeln!(f, "  no {:?}", distances.item)?;
+                    continue;
+                };
+                writeln!(
+                    f,
+                    "  {} {:?}, nearest {nearest} moves away, {mean:.1} on \
+                     average",
+                    distances.reachable, distances.item
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the level with `seed`, and then with the seeds after it until the
+/// map meets `constraints`. Returns the seed that did with its report.
+pub fn find_valid_seed(
+    levels: &LevelRegistry,
+    level: &LevelArgs,
+    seed: Seed,
+    constraints: &MapConstraints,
+) -> eyre::Result<(Seed, MapReport)> {
+    let mut violations = Vec::new();
+    let attempts = constraints.max_attempts.max(1);
+    for attempt in 0..attempts {
+        let seed = Seed(seed.0.wrapping_add(attempt));
+        let mut world = levels.build_detached(level, seed)?;
+        let report = MapReport::of_world(&mut world);
+        violations = constraints.violations(&report);
+        if violations.is_empty() {
+            return Ok((seed, report));
+        }
+    }
+    bail!(
+        "No map in {attempts} tries met the constraints. The last one had:\n{}",
+        violations.join("\n")
+    );
+}
+
+/// Builds the level without opening a window and prints what can be reached
+/// from the spawns
+pub fn check_level(
+    levels: &LevelRegistry,
+    level: &LevelArgs,
+    seed: Seed,
+    constraints: &MapConstraints,
+) -> eyre::Result<()> {
+    let (seed, report) = find_valid_seed(levels, level, seed, constraints)?;
+    println!("Level {} with seed {}", level.name, seed.0);
+    print!("{report}");
+    Ok(())
+}
+
+/// Logs what can be reached from the spawns of the level that was built
+pub(super) fn report_level(grid_world: Res<GridWorld>, bots: Query<&BotData>) {
+    let spawns = bots.iter().map(|bot_data| (bot_data.pos, bot_data.team));
+    let report = MapReport::new(&grid_world, spawns);
+    if report.unreachable_items.is_empty()
+        && report.unreachable_areas.is_empty()
+    {
+        info!("Map check:\n{report}");
+    } else {
+        warn!("Map check:\n{report}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CellState;
+
+    /// A blocked map with the cells from `x` to `to_x` open along `y = 1`
+    fn corridors(width: usize, open: &[(usize, usize)]) -> GridWorld {
+        let mut grid_world = GridWorld::new(width, 3, CellState::blocked());
+        for (x, to_x) in open {
+            for x in *x..=*to_x {
+                grid_world.set_tuple(x, 1, CellState::empty());
+            }
+        }
+        grid_world
+    }
+
+    #[test]
+    fn reports_sealed_items() {
+        let mut grid_world = corridors(7, &[(1, 2), (4, 5)]);
+        grid_world.get_tuple_mut(5, 1).item = Some(Item::Fent);
+
+        let report = MapReport::new(&grid_world, [(Pos((1, 1)), Team::Player)]);
+        assert_eq!(report.unreachable_items, vec![(Pos((5, 1)), Item::Fent)]);
+        assert_eq!(report.unreachable_areas, vec![(2, Pos((4, 1)))]);
+
+        let constraints = MapConstraints {
+            all_reachable: true,
+            max_distance_gap: None,
+            max_attempts: 1,
+        };
+        assert_eq!(constraints.violations(&report).len(), 1);
+    }
+
+    #[test]
+    fn asymmetric_map_exceeds_the_distance_gap() {
+        let mut grid_world = corridors(12, &[(1, 10)]);
+        grid_world.get_tuple_mut(2, 1).item = Some(Item::Metal);
+        let spawns = [(Pos((1, 1)), Team::Player), (Pos((10, 1)), Team::Enemy)];
+        let report = MapReport::new(&grid_world, spawns);
+
+        let mut constraints = MapConstraints {
+            all_reachable: true,
+            max_distance_gap: Some(3.0),
+            max_attempts: 1,
+        };
+        // The Enemy is 8 moves from the Metal and the Player 1
+        assert_eq!(constraints.violations(&report).len(), 1);
+        constraints.max_distance_gap = Some(7.0);
+        assert!(constraints.violations(&report).is_empty());
+    }
+}
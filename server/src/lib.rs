@@ -0,0 +1,679 @@
+#![allow(unused_imports, dead_code)]
+#![feature(mpmc_channel)]
+#![feature(arbitrary_self_types)]
+
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use argh::FromArgs;
+use bevy::{color::palettes::css, prelude::*};
+use editor::{EditedMap, EditorPlugin};
+use game::{
+    apply_actions::ActionsPlugin,
+    bot_lib::{
+        parse_bot_params,
+        rules,
+        BotLibPlugin,
+        MatchSetup,
+        DEFAULT_BOT_LIB_PATH,
+    },
+    bot_update::{crash_summary, BotCrashes, BotId, BotUpdatePlugin},
+    core::{CorePlugin, CoreSystemsSet},
+    time_budget::{BudgetEnforcement, TimeBudget},
+};
+use graphics::GraphicsSystemSet;
+use levels::{
+    check_level,
+    find_valid_seed,
+    LevelArgs,
+    LevelRegistry,
+    LevelsPlugin,
+    MapConstraints,
+};
+use replay::{
+    debug_bot_update,
+    diff_replays,
+    export_replay,
+    read_replay_header,
+    render_replay,
+    ExportFormat,
+    ReplayKind,
+    ReplayPlugin,
+    ReplaySystemSet,
+};
+use serde::{Deserialize, Serialize};
+use swarm_lib::{BotData, Item, Pos, Team};
+use types::{Seed, Tick};
+
+mod editor;
+mod game;
+mod graphics;
+mod levels;
+mod replay;
+#[cfg(test)]
+mod scenario;
+mod types;
+
+// What another crate needs to add a level with `LevelRegistry::register`
+pub use graphics::tilemap::MapSize;
+pub use levels::{
+    ItemDistances,
+    Level,
+    LevelArgs,
+    LevelRegistry,
+    MapReport,
+    TeamDistances,
+};
+pub use types::{CellState, GridWorld, Seed};
+
+#[derive(FromArgs)]
+/// Swarm Server
+struct Args {
+    #[argh(positional, greedy)]
+    /// the level to load followed by its options, such as `econ-loop --width
+    /// 50`. Leave out to only look at --replay. Run with an unknown level to
+    /// list the levels
+    pub level: Vec<String>,
+
+    #[argh(option)]
+    /// the replay file to load
+    pub replay: Option<String>,
+
+    #[argh(option)]
+    /// the file to record the replay to. Defaults to a new timestamped file
+    /// in replays/
+    pub save_replay: Option<String>,
+
+    #[argh(switch)]
+    /// don't record a replay
+    pub no_replay: bool,
+
+    #[argh(switch)]
+    /// record only the bots' actions instead of the full state. Playing it
+    /// back re-simulates the match and checks it ends up in the same state
+    pub input_replay: bool,
+
+    #[argh(option)]
+    /// draw every tick of --replay without a window, to a GIF if this ends in
+    /// .gif or else to PNG frames in this directory, and exit
+    pub render: Option<String>,
+
+    #[argh(option, default = "8")]
+    /// the size in pixels of a cell in --render
+    pub render_scale: u32,
+
+    #[argh(option)]
+    /// draw --render as this bot saw it, hiding what it hadn't seen
+    pub render_bot: Option<u32>,
+
+    #[argh(option)]
+    /// write the bots, team totals and events of --replay to files in this
+    /// directory, and exit
+    pub export: Option<String>,
+
+    #[argh(option, default = "ExportFormat::JsonLines")]
+    /// the file format for --export: jsonl or csv
+    pub export_format: ExportFormat,
+
+    #[argh(option)]
+    /// compare --replay with this replay of the same level and seed, print
+    /// where they diverge and how the outcomes differ, and exit
+    pub diff_replay: Option<String>,
+
+    #[argh(option)]
+    /// re-run the update of this bot at --debug-tick from --replay with the
+    /// bot library, print its action and logs, and exit
+    pub debug_bot: Option<u32>,
+
+    #[argh(option)]
+    /// the tick to re-run for --debug-bot
+    pub debug_tick: Option<u32>,
+
+    #[argh(switch)]
+    /// build the level, print which items can't be reached from the spawns
+    /// and how far each team is from the items, and exit
+    pub check_level: bool,
+
+    #[argh(switch)]
+    /// generate the level again with the next seed until every item can be
+    /// reached from a spawn
+    pub require_reachable: bool,
+
+    #[argh(option)]
+    /// generate the level again with the next seed until the teams' average
+    /// distances to each kind of item differ by at most this many cells
+    pub max_distance_gap: Option<f32>,
+
+    #[argh(option, default = "20")]
+    /// the number of seeds to try for --require-reachable and
+    /// --max-distance-gap
+    pub max_map_attempts: u64,
+
+    #[argh(option)]
+    /// open this map file in the editor instead of playing a level, or start
+    /// a new --width by --height map if it doesn't exist. Ctrl+S saves to it
+    pub edit: Option<String>,
+
+    #[argh(option, default = "500")]
+    /// the tick rate in milliseconds
+    pub tick_ms: u64,
+
+    #[argh(option)]
+    /// the width of the map
+    pub width: Option<usize>,
+
+    #[argh(option)]
+    /// the height of the map
+    pub height: Option<usize>,
+
+    #[argh(option, default = "String::from(DEFAULT_BOT_LIB_PATH)")]
+    /// the bot library to load. It is reloaded when the file changes
+    pub bot_lib: String,
+
+    #[argh(option)]
+    /// a key=value parameter handed to every bot when it is created, such
+    /// as `scouts=3`. Can be given more than once
+    pub bot_param: Vec<String>,
+
+    #[argh(option)]
+    /// the seed for everything random in the match. Random if not given
+    pub seed: Option<u64>,
+
+    #[argh(option, default = "20")]
+    /// the CPU time in milliseconds a bot may spend in one update before
+    /// drawing on its time bank
+    pub tick_budget_ms: u64,
+
+    #[argh(option, default = "1000")]
+    /// the CPU time in milliseconds a bot may spend over the per tick budget
+    /// during the whole match
+    pub time_bank_ms: u64,
+
+    #[argh(option, default = "BudgetEnforcement::Off")]
+    /// how bots that run out of time are penalised: off, skip-action or
+    /// noop-next-tick. Off by default, so out of the box slow bots are only
+    /// measured and never penalised. The budget is on CPU time, but even
+    /// that varies between runs, so enforcing it can make the same seed play
+    /// out differently
+    pub budget_enforcement: BudgetEnforcement,
+}
+
+/// Runs the server with the command line arguments. `levels` are the ones
+/// that can be picked, so that other crates can add their own to
+/// `LevelRegistry::default()`.
+pub fn run(levels: LevelRegistry) {
+    let args: Args = argh::from_env();
+    if args.no_replay && args.save_replay.is_some() {
+        eprintln!("--save-replay and --no-replay can't be used together");
+        std::process::exit(1);
+    }
+    if args.replay.is_some() && !args.bot_param.is_empty() {
+        eprintln!(
+            "--bot-param can't be used with --replay, the bots get the params \
+             the replay was recorded with"
+        );
+        std::process::exit(1);
+    }
+    let mut level = LevelArgs::from_command_line(&args.level);
+    let mut seed = args.seed;
+    let time_budget = TimeBudget {
+        per_tick: Duration::from_millis(args.tick_budget_ms),
+        bank: Duration::from_millis(args.time_bank_ms),
+        enforcement: args.budget_enforcement,
+    };
+
+    if let Some(output) = &args.render {
+        let Some(replay) = &args.replay else {
+            eprintln!("--render needs --replay");
+            std::process::exit(1);
+        };
+        let fog_of_war_for = args.render_bot.map(BotId);
+        if let Err(err) =
+            render_replay(replay, output, args.render_scale, fog_of_war_for)
+        {
+            eprintln!("Could not render replay: {err:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(dir) = &args.export {
+        let Some(replay) = &args.replay else {
+            eprintln!("--export needs --replay");
+            std::process::exit(1);
+        };
+        if let Err(err) = export_replay(replay, dir, args.export_format) {
+            eprintln!("Could not export replay: {err:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(other) = &args.diff_replay {
+        let Some(replay) = &args.replay else {
+            eprintln!("--diff-replay needs --replay");
+            std::process::exit(1);
+        };
+        if let Err(err) = diff_replays(replay, other) {
+            eprintln!("Could not diff replays: {err:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(bot_id) = args.debug_bot {
+        let (Some(replay), Some(tick)) = (&args.replay, args.debug_tick) else {
+            eprintln!("--debug-bot needs --replay and --debug-tick");
+            std::process::exit(1);
+        };
+        if let Err(err) =
+            debug_bot_update(replay, &args.bot_lib, BotId(bot_id), tick)
+        {
+            eprintln!("Could not debug bot {bot_id}: {err:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let editor = args.edit.as_ref().map(|path| {
+        if level.is_some() || args.replay.is_some() {
+            eprintln!(
+                "--edit opens a map on its own, without a level or --replay"
+            );
+            std::process::exit(1);
+        }
+        let (width, height) =
+            (args.width.unwrap_or(20), args.height.unwrap_or(20));
+        EditedMap::open(path, width, height).unwrap_or_else(|err| {
+            eprintln!("Could not open map {path}: {err:#}");
+            std::process::exit(1);
+        })
+    });
+
+    let mut bot_params =
+        parse_bot_params(&args.bot_param).unwrap_or_else(|err| {
+            eprintln!("{err:#}");
+            std::process::exit(1);
+        });
+    let mut recorded_level = None;
+    let mut recorded_rules = None;
+
+    // An input replay is played back by running the recorded level again
+    if let Some(path) = &args.replay {
+        let header = read_replay_header(path).unwrap_or_else(|err| {
+            panic!("Could not load replay {path}: {err:#}")
+        });
+        // Bots are created again like the recorded ones, also when branching
+        // from a snapshot replay
+        bot_params = header.bot_params;
+        recorded_level = Some(header.level.name.clone());
+        recorded_rules = Some(header.rules);
+        seed = Some(header.seed);
+        if header.kind == ReplayKind::Inputs {
+            level = Some(header.level);
+        }
+    } else if level.is_none() && editor.is_none() {
+        eprintln!(
+            "Pick a level, a --replay or a map to --edit. Levels are:\n{}",
+            levels.list()
+        );
+        std::process::exit(1);
+    }
+
+    if let Some(level) = &level {
+        if let Err(exit) = levels.check(level) {
+            match exit.status {
+                Ok(()) => println!("{}", exit.output),
+                Err(()) => eprintln!("{}", exit.output),
+            }
+            std::process::exit(exit.status.map_or(1, |()| 0));
+        }
+    }
+
+    let mut seed = Seed(seed.unwrap_or_else(rand::random));
+    let constraints = MapConstraints {
+        all_reachable: args.require_reachable,
+        max_distance_gap: args.max_distance_gap,
+        max_attempts: args.max_map_attempts,
+    };
+    if args.check_level {
+        let Some(level) = &level else {
+            eprintln!("--check-level needs a level");
+            std::process::exit(1);
+        };
+        if let Err(err) = check_level(&levels, level, seed, &constraints) {
+            eprintln!("Could not check level {}: {err:#}", level.name);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Replays are played back with the seed they were recorded with
+    if let (Some(level), None) = (&level, &args.replay) {
+        if !constraints.is_empty() {
+            match find_valid_seed(&levels, level, seed, &constraints) {
+                Ok((valid_seed, _)) => seed = valid_seed,
+                Err(err) => {
+                    eprintln!(
+                        "Could not generate level {}: {err:#}",
+                        level.name
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    let setup = MatchSetup {
+        level: level
+            .as_ref()
+            .map(|level| level.name.clone())
+            .or(recorded_level)
+            .unwrap_or_default(),
+        seed,
+        bot_params,
+        rules: recorded_rules.unwrap_or_else(|| {
+            let win_condition = match &level {
+                Some(level) => {
+                    levels.win_condition(level).unwrap_or_else(|err| {
+                        eprintln!(
+                            "Could not get the win condition of {}: {err:#}",
+                            level.name
+                        );
+                        std::process::exit(1);
+                    })
+                }
+                None => WinCondition::default(),
+            };
+            rules(&win_condition, &time_budget)
+        }),
+    };
+
+    let scale = 32.0;
+    let map_size = match &editor {
+        Some(editor) => Some((editor.map.width(), editor.map.height())),
+        None => level.as_ref().and_then(|level| levels.map_size(level)),
+    };
+    let res = match map_size {
+        Some((width, height)) => (width as f32 * scale, height as f32 * scale),
+        None => (500.0, 500.0),
+    };
+    let res = (res.0.min(2231.0) + 2.0, res.1.min(1485.0) + 2.0);
+
+    App::new()
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    resolution: res.into(),
+                    ..default()
+                }),
+                ..default()
+            }),
+            bevy_pancam::PanCamPlugin,
+        ))
+        .add_plugins((
+            graphics::GraphicsPlugin,
+            ActionsPlugin,
+            CorePlugin,
+            LevelsPlugin { levels, level },
+            EditorPlugin { map: editor },
+            BotLibPlugin {
+                bot_lib_path: args.bot_lib,
+            },
+            BotUpdatePlugin,
+            ReplayPlugin {
+                save_replay: args.save_replay,
+                record_replay: !args.no_replay,
+                input_replay: args.input_replay,
+                load_replay: args.replay,
+            },
+        ))
+        .init_resource::<WinCondition>()
+        .insert_state(GameState::Idle)
+        .insert_resource(TickSpeed {
+            ms: args.tick_ms,
+            is_paused: false,
+        })
+        .insert_resource(seed)
+        .insert_resource(setup)
+        .insert_resource(time_budget)
+        .add_systems(Startup, camera_setup)
+        .add_systems(
+            OnExit(GameState::InGame),
+            |mut commands: Commands, pawns: Query<Entity, With<BotData>>| {
+                for pawn in pawns.iter() {
+                    commands.entity(pawn).despawn_recursive();
+                }
+            },
+        )
+        .configure_sets(
+            Update,
+            (
+                (
+                    TickSystemSet.run_if(should_tick),
+                    (CoreSystemsSet, ReplaySystemSet)
+                        .chain()
+                        .run_if(resource_changed::<Tick>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+                GraphicsSystemSet.run_if(GameState::shows_map),
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                update_tick.in_set(TickSystemSet),
+                exit_system,
+                check_win_condition,
+                display_win_ui,
+            ),
+        )
+        .run();
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+struct TickSystemSet;
+
+#[derive(Resource)]
+pub struct TickSpeed {
+    pub ms: u64,
+    pub is_paused: bool,
+}
+
+pub fn should_tick(
+    tick_ms: Res<TickSpeed>,
+    time: Res<Time>,
+    mut timer: Local<Timer>,
+) -> bool {
+    if tick_ms.is_paused {
+        return false;
+    }
+    timer.tick(time.delta());
+    if timer.just_finished() {
+        timer.set_duration(Duration::from_millis(tick_ms.ms));
+        timer.reset();
+        return true;
+    }
+    false
+}
+
+fn update_tick(mut tick: ResMut<Tick>) {
+    tick.0 += 1;
+}
+
+#[derive(States, Hash, Eq, PartialEq, Clone, Debug, Default)]
+pub enum GameState {
+    #[default]
+    Idle,
+    InGame,
+    /// Drawing a map in the editor, with nothing being simulated
+    Editing,
+}
+
+impl GameState {
+    /// Run condition for drawing the map, which both a match and the editor
+    /// do
+    pub fn shows_map(state: Res<State<GameState>>) -> bool {
+        matches!(state.get(), GameState::InGame | GameState::Editing)
+    }
+}
+
+pub fn camera_setup(mut commands: Commands) {
+    commands.spawn((
+        Camera2d,
+        bevy_pancam::PanCam {
+            move_keys: bevy_pancam::DirectionKeys::wasd(),
+            grab_buttons: vec![MouseButton::Right, MouseButton::Left],
+            min_scale: 0.25,
+            max_scale: 5.0,
+            ..default()
+        },
+    ));
+}
+
+pub fn exit_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if keys.all_pressed([KeyCode::ControlLeft, KeyCode::KeyC]) {
+        exit.send(AppExit::Success);
+    }
+}
+
+#[derive(Resource)]
+pub struct Won(pub Team);
+
+/// Summary of a finished match, captured before the bots are despawned
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MatchResults {
+    pub crashes: Vec<String>,
+}
+
+#[derive(Component)]
+struct WinDisplay;
+
+/// What a bot has to hold to win the match for its team
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WinCondition {
+    pub hold: HashMap<Item, u8>,
+}
+
+impl Default for WinCondition {
+    /// The Fent and 2 Truffles
+    fn default() -> Self {
+        WinCondition {
+            hold: HashMap::from([(Item::Fent, 1), (Item::Truffle, 2)]),
+        }
+    }
+}
+
+impl WinCondition {
+    pub fn is_met(&self, bot_data: &BotData) -> bool {
+        self.hold
+            .iter()
+            .all(|(item, count)| bot_data.inventory.get(*item) >= *count)
+    }
+}
+
+fn check_win_condition(
+    mut commands: Commands,
+    query: Query<(&BotId, &BotData)>,
+    crashes: Query<(&BotId, &BotCrashes)>,
+    win_condition: Res<WinCondition>,
+    won: Option<Res<Won>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if won.is_some() {
+        return;
+    }
+    for (bot_id, bot_data) in query.iter() {
+        if !win_condition.is_met(bot_data) {
+            continue;
+        }
+        info!(
+            "Team {team} won! Bot {bot_id:?} holds {hold:?}",
+            team = bot_data.team,
+            hold = win_condition.hold
+        );
+        let results = MatchResults {
+            crashes: crash_summary(crashes.iter()),
+        };
+        for line in &results.crashes {
+            info!("{line}");
+        }
+        commands.insert_resource(results);
+        commands.insert_resource(Won(bot_data.team));
+        next_state.set(GameState::Idle);
+    }
+}
+
+fn display_win_ui(
+    mut commands: Commands,
+    won: Option<Res<Won>>,
+    query: Query<Entity, With<WinDisplay>>,
+    results: Option<Res<MatchResults>>,
+) {
+    // Only create UI if we have a win and haven't created the UI yet
+    if won.is_some() && query.is_empty() {
+        let team = match won.unwrap().0 {
+            Team::Player => "Player",
+            Team::Enemy => "Enemy",
+        };
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(50.0),
+                    height: Val::Percent(25.0),
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(25.0),
+                    top: Val::Percent(35.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.2, 0.2, 0.2, 0.9)),
+                WinDisplay,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text::new(format!("Team {} Won!", team)),
+                    TextFont {
+                        font_size: 32.0,
+                        ..default()
+                    },
+                    TextColor(Color::WHITE),
+                ));
+
+                let crashes =
+                    results.iter().flat_map(|results| results.crashes.iter());
+                for line in crashes {
+                    parent.spawn((
+                        Text::new(line),
+                        TextFont {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(1.0, 0.6, 0.6)),
+                    ));
+                }
+
+                parent.spawn((
+                    Text::new("Press Ctrl+C to exit"),
+                    TextFont {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                    TextColor(Color::WHITE),
+                ));
+            });
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use super::{LiveOrReplay, Replay};
+use crate::{
+    game::{
+        bot_lib::BotLib,
+        bot_update::{BotCrashes, BotId, BotInstance, NextBotId},
+    },
+    types::Tick,
+};
+
+/// Pressing R while playing back a replay branches a live match off the
+/// tick on screen
+pub(super) fn branch_from_replay(
+    keys: Res<ButtonInput<KeyCode>>,
+    tick: Res<Tick>,
+    mut next_state: ResMut<NextState<LiveOrReplay>>,
+) {
+    if keys.just_pressed(KeyCode::KeyR) {
+        info!(tick = tick.0, "Resuming a live match from the replay");
+        next_state.set(LiveOrReplay::Live);
+    }
+}
+
+/// Hands every bot restored from the replay a fresh instance from the
+/// current bot library, so a fixed bot can be tried on the exact same
+/// situation.
+///
+/// The bots in the replay never ran in this process, so there is no state to
+/// carry over like there is on a hot reload.
+pub(super) fn resume_live(
+    bot_lib: Res<BotLib>,
+    mut bots: Query<(&BotId, &mut BotInstance, &mut BotCrashes)>,
+    mut next_bot_id: ResMut<NextBotId>,
+    mut replay: ResMut<Replay>,
+) {
+    for (bot_id, mut bot_instance, mut bot_crashes) in bots.iter_mut() {
+        let config = bot_instance.config.clone();
+        bot_instance.bot = bot_lib.new_bot(bot_id.0, config);
+        // Crashes are kept for reference, but the new build gets a chance
+        bot_crashes.disabled = false;
+    }
+
+    // Bots built from here on must not reuse the ids of replayed bots
+    let max_bot_id = bots.iter().map(|(bot_id, ..)| bot_id.0 + 1).max();
+    next_bot_id.0 = next_bot_id.0.max(max_bot_id.unwrap_or(0));
+
+    // The branch isn't recorded, the state from the replay is no longer
+    // needed
+    replay.reader = None;
+    replay.current = None;
+    info!(bots = bots.iter().count(), "Live match resumed");
+}
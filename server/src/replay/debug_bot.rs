@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use eyre::bail;
+use swarm_lib::{ActionResult, ActionWithId, BotUpdate, MatchInfo};
+
+use super::{load_replay, BotComponents};
+use crate::{
+    game::{
+        bot_lib::{BotLib, MatchSetup},
+        bot_update::BotId,
+    },
+    types::Seed,
+};
+
+/// Re-runs one bot's update from a replay with a freshly loaded bot library,
+/// and prints the action it picks and what it logs.
+///
+/// Bots that keep state between updates start from scratch, so they only
+/// see what the replay recorded about this one tick.
+pub fn debug_bot_update(
+    replay_path: &str,
+    bot_lib_path: &str,
+    bot_id: BotId,
+    tick: u32,
+) -> eyre::Result<()> {
+    let mut replay = load_replay(replay_path)?;
+    let Some(tick_data) = replay.tick_data(tick, None)? else {
+        bail!("Replay starts after tick {tick}");
+    };
+    if tick_data.tick != tick {
+        bail!("Tick {tick} isn't in the replay");
+    }
+    let Some(bot) = tick_data.bot_data.get(&bot_id) else {
+        bail!("Bot {} doesn't exist at tick {tick}", bot_id.0);
+    };
+    let prev = match tick.checked_sub(1) {
+        Some(prev) => replay.tick_data(prev, None)?,
+        None => None,
+    }
+    .filter(|prev| prev.tick + 1 == tick);
+    let prev_bot = prev.as_ref().and_then(|prev| prev.bot_data.get(&bot_id));
+
+    let header = replay.header.as_ref().expect("Set when loaded");
+    let match_info = Arc::new(header.match_info.clone());
+    let setup = MatchSetup {
+        level: header.level.name.clone(),
+        seed: Seed(header.seed),
+        bot_params: header.bot_params.clone(),
+        rules: header.rules.clone(),
+    };
+    let map_size =
+        (tick_data.grid_world.width(), tick_data.grid_world.height());
+    let config = setup.bot_config(
+        bot_id,
+        bot.bot_data.team,
+        map_size,
+        match_info.clone(),
+    );
+
+    let update = bot_update_at(tick, bot, prev_bot, match_info);
+    println!("Bot {} at tick {tick}", bot_id.0);
+    println!("  in progress: {:?}", update.in_progress_action);
+    println!("  completed: {:?}", update.completed_action);
+
+    let bot_lib = BotLib::load(bot_lib_path);
+    let mut instance = bot_lib.new_bot(bot_id.0, config);
+    let (action, logs) = instance.update(update);
+
+    match action {
+        Some(ActionWithId { id, action, reason }) => {
+            println!("Action {id}: {action:?} ({reason})")
+        }
+        None => println!("No action"),
+    }
+    for log in logs {
+        println!("[{:?}] {}", log.level, log.message);
+        for (key, value) in log.attrs.iter().flatten() {
+            println!("    {key} = {value}");
+        }
+    }
+
+    // Dropped while the library is still loaded
+    drop(instance);
+    drop(bot_lib);
+    Ok(())
+}
+
+/// The `BotUpdate` the bot received at `tick`.
+///
+/// The replay has the state after the bot's update, so the action it
+/// submitted that tick is undone. A submitted action cancels the one in
+/// progress, which is the only way an action is cancelled.
+fn bot_update_at(
+    tick: u32,
+    bot: &BotComponents,
+    prev_bot: Option<&BotComponents>,
+    match_info: Arc<MatchInfo>,
+) -> BotUpdate {
+    let mut new_past_actions = match prev_bot {
+        Some(prev_bot) => bot
+            .past_actions
+            .get(prev_bot.past_actions.len()..)
+            .unwrap_or_default(),
+        None => bot
+            .past_actions
+            .iter()
+            .rposition(|action| action.completed_tick != tick)
+            .map_or(&bot.past_actions[..], |idx| &bot.past_actions[idx + 1..]),
+    };
+
+    let cancelled = match new_past_actions.split_last() {
+        Some((last, rest)) if last.status.is_cancelled() => {
+            new_past_actions = rest;
+            Some(last)
+        }
+        _ => None,
+    };
+
+    let in_progress_action = if let Some(cancelled) = cancelled {
+        Some(ActionWithId {
+            id: cancelled.id,
+            action: cancelled.action.clone(),
+            reason: cancelled.reason.as_str(),
+        })
+    } else if !new_past_actions.is_empty() {
+        // The action in progress finished this tick
+        None
+    } else {
+        // Without the previous tick, assume the current action wasn't just
+        // submitted
+        prev_bot
+            .unwrap_or(bot)
+            .current_action
+            .as_ref()
+            .map(|action| ActionWithId {
+                id: action.id,
+                action: action.kind.clone(),
+                reason: action.reason.as_str(),
+            })
+    };
+
+    BotUpdate {
+        tick,
+        bot_data: bot.bot_data.clone(),
+        match_info,
+        in_progress_action,
+        completed_action: new_past_actions
+            .last()
+            .filter(|action| action.completed_tick == tick)
+            .map(|action| ActionResult {
+                action: action.action.clone(),
+                id: action.id,
+                status: action.status.clone(),
+                reason: action.reason,
+                completed_tick: action.completed_tick,
+            }),
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::{ecs::entity::EntityHashMap, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use swarm_lib::{
+    chunked_grid::ChunkedGridDelta,
+    known_map::{ClientCellState, KnownGrid},
+    ActionResult,
+    BotData,
+};
+
+use super::{BotComponents, TickData};
+use crate::{
+    game::{
+        apply_actions::CurrentAction,
+        bot_update::{BotCrashes, BotId, BotLogs},
+        time_budget::{BotTimeStats, TeamTimeStats},
+    },
+    types::{CellState, PartiallyBuiltBot},
+};
+
+/// The full state is recorded every this many ticks, so restoring any tick
+/// applies at most this many deltas
+pub const KEYFRAME_INTERVAL: u32 = 50;
+
+/// One tick of a replay file
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ReplayRecord {
+    Keyframe(TickData),
+    Delta(TickDelta),
+}
+
+impl ReplayRecord {
+    pub fn is_keyframe(&self) -> bool {
+        matches!(self, ReplayRecord::Keyframe(_))
+    }
+
+    pub fn tick(&self) -> u32 {
+        match self {
+            ReplayRecord::Keyframe(tick_data) => tick_data.tick,
+            ReplayRecord::Delta(delta) => delta.tick,
+        }
+    }
+}
+
+/// Everything that changed from one tick to the next
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TickDelta {
+    pub(super) tick: u32,
+    grid_cells: Vec<((usize, usize), CellState)>,
+    pub(super) bots: HashMap<BotId, BotDelta>,
+    pub(super) new_bots: HashMap<BotId, BotComponents>,
+    pub(super) removed_bots: Vec<BotId>,
+    partially_built_bots: EntityHashMap<PartiallyBuiltBot>,
+    team_time_stats: TeamTimeStats,
+}
+
+/// A bot's components, leaving out the known map chunks and past actions
+/// that were already recorded
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct BotDelta {
+    /// Has an empty known map, the changes are in `known_map`
+    pub(super) bot_data: BotData,
+    known_map: ChunkedGridDelta<ClientCellState>,
+    current_action: CurrentAction,
+    /// Actions completed since the previous tick, or all of them if
+    /// `past_actions_reset`
+    pub(super) new_past_actions: Vec<ActionResult>,
+    /// The past actions got shorter, for example because the bot id was
+    /// reused, so they don't continue the previous tick's
+    pub(super) past_actions_reset: bool,
+    bot_logs: BotLogs,
+    bot_crashes: BotCrashes,
+    bot_time_stats: BotTimeStats,
+}
+
+impl TickDelta {
+    pub fn new(prev: &TickData, next: &TickData) -> Self {
+        let grid_cells = next
+            .grid_world
+            .iter()
+            .zip(prev.grid_world.iter())
+            .filter(|((_, next), (_, prev))| next != prev)
+            .map(|((pos, cell), _)| (pos, *cell))
+            .collect();
+
+        let mut bots = HashMap::default();
+        let mut new_bots = HashMap::default();
+        for (bot_id, components) in &next.bot_data {
+            match prev.bot_data.get(bot_id) {
+                Some(prev) => {
+                    bots.insert(*bot_id, BotDelta::new(prev, components));
+                }
+                None => {
+                    new_bots.insert(*bot_id, components.clone());
+                }
+            }
+        }
+
+        let removed_bots = prev
+            .bot_data
+            .keys()
+            .filter(|bot_id| !next.bot_data.contains_key(*bot_id))
+            .copied()
+            .collect();
+
+        TickDelta {
+            tick: next.tick,
+            grid_cells,
+            bots,
+            new_bots,
+            removed_bots,
+            partially_built_bots: next.partially_built_bots.clone(),
+            team_time_stats: next.team_time_stats.clone(),
+        }
+    }
+
+    /// Turns the state at the previous tick into the state at this one
+    pub fn apply(&self, tick_data: &mut TickData) {
+        tick_data.tick = self.tick;
+
+        for ((x, y), cell) in &self.grid_cells {
+            tick_data.grid_world.set_tuple(*x, *y, *cell);
+        }
+
+        for bot_id in &self.removed_bots {
+            tick_data.bot_data.remove(bot_id);
+        }
+        for (bot_id, delta) in &self.bots {
+            let components = tick_data
+                .bot_data
+                .get_mut(bot_id)
+                .expect("Delta for a bot that isn't in the previous tick");
+            delta.apply(components);
+        }
+        tick_data.bot_data.extend(
+            self.new_bots
+                .iter()
+                .map(|(bot_id, components)| (*bot_id, components.clone())),
+        );
+
+        tick_data.partially_built_bots = self.partially_built_bots.clone();
+        tick_data.team_time_stats = self.team_time_stats.clone();
+    }
+}
+
+impl BotDelta {
+    fn new(prev: &BotComponents, next: &BotComponents) -> Self {
+        let mut bot_data = next.bot_data.clone();
+        bot_data.known_map.map = empty_known_grid();
+
+        BotDelta {
+            bot_data,
+            known_map: next
+                .bot_data
+                .known_map
+                .map
+                .grid
+                .delta_from(&prev.bot_data.known_map.map.grid),
+            current_action: next.current_action.clone(),
+            new_past_actions: next
+                .past_actions
+                .get(prev.past_actions.len()..)
+                .unwrap_or(&next.past_actions[..])
+                .to_vec(),
+            past_actions_reset: next.past_actions.len()
+                < prev.past_actions.len(),
+            bot_logs: next.bot_logs.clone(),
+            bot_crashes: next.bot_crashes.clone(),
+            bot_time_stats: next.bot_time_stats.clone(),
+        }
+    }
+
+    fn apply(&self, components: &mut BotComponents) {
+        let mut known_map = std::mem::replace(
+            &mut components.bot_data.known_map.map,
+            empty_known_grid(),
+        );
+        known_map.grid.apply_delta(&self.known_map);
+
+        components.bot_data = self.bot_data.clone();
+        components.bot_data.known_map.map = known_map;
+        components.current_action = self.current_action.clone();
+        if self.past_actions_reset {
+            components.past_actions.0.clear();
+        }
+        components
+            .past_actions
+            .0
+            .extend(self.new_past_actions.iter().cloned());
+        components.bot_logs = self.bot_logs.clone();
+        components.bot_crashes = self.bot_crashes.clone();
+        components.bot_time_stats = self.bot_time_stats.clone();
+    }
+}
+
+fn empty_known_grid() -> KnownGrid {
+    KnownGrid::with_storage(0, 0, default())
+}
@@ -0,0 +1,248 @@
+use std::{collections::BTreeMap, iter::Peekable};
+
+use bevy::{prelude::*, utils::HashMap};
+use eyre::bail;
+use swarm_lib::{BotData, Item, Team};
+
+use super::{inputs::checksum, load_replay, TickData};
+use crate::{game::bot_update::BotId, WinCondition};
+
+/// Compares two replays of the same level and seed, such as before and after
+/// a bot change, and prints where they diverge and how their outcomes differ
+pub fn diff_replays(path_a: &str, path_b: &str) -> eyre::Result<()> {
+    let mut replay_a = load_replay(path_a)?;
+    let mut replay_b = load_replay(path_b)?;
+    let (Some(header_a), Some(header_b)) = (&replay_a.header, &replay_b.header)
+    else {
+        unreachable!("Loaded replays have a header");
+    };
+    if header_a.level != header_b.level || header_a.seed != header_b.seed {
+        bail!(
+            "The replays aren't of the same match: {:?} with seed {} vs {:?} \
+             with seed {}",
+            header_a.level,
+            header_a.seed,
+            header_b.level,
+            header_b.seed
+        );
+    }
+
+    let win_condition = header_a.win_condition.clone();
+    let mut summary_a = Summary::new(win_condition.clone());
+    let mut summary_b = Summary::new(win_condition);
+    let mut diverged_at = None;
+    // First tick each bot's actions differ at, with both actions
+    let mut action_diffs = BTreeMap::<u32, (u32, String, String)>::new();
+
+    let mut ticks_a = replay_a.ticks().peekable();
+    let mut ticks_b = replay_b.ticks().peekable();
+    loop {
+        let tick_a = peek_tick(&mut ticks_a)?;
+        let tick_b = peek_tick(&mut ticks_b)?;
+        match (tick_a, tick_b) {
+            (None, None) => break,
+            (Some(_), None) => summary_a.add(&ticks_a.next().unwrap()?),
+            (None, Some(_)) => summary_b.add(&ticks_b.next().unwrap()?),
+            (Some(a), Some(b)) if a < b => {
+                summary_a.add(&ticks_a.next().unwrap()?)
+            }
+            (Some(a), Some(b)) if b < a => {
+                summary_b.add(&ticks_b.next().unwrap()?)
+            }
+            (Some(_), Some(_)) => {
+                let a = ticks_a.next().unwrap()?;
+                let b = ticks_b.next().unwrap()?;
+                if diverged_at.is_none()
+                    && tick_checksum(&a) != tick_checksum(&b)
+                {
+                    diverged_at = Some(a.tick);
+                }
+                for (bot_id, action_a, action_b) in action_diffs_at(&a, &b) {
+                    action_diffs
+                        .entry(bot_id.0)
+                        .or_insert((a.tick, action_a, action_b));
+                }
+                summary_a.add(&a);
+                summary_b.add(&b);
+            }
+        }
+    }
+
+    println!("A: {path_a}");
+    println!("B: {path_b}");
+    match diverged_at {
+        Some(tick) => println!("World state diverges at tick {tick}"),
+        None => println!("World state is the same on every common tick"),
+    }
+    if action_diffs.is_empty() {
+        println!("No bot acted differently");
+    }
+    for (bot_id, (tick, action_a, action_b)) in &action_diffs {
+        println!(
+            "Bot {bot_id} first acts differently at tick {tick}: {action_a} \
+             vs {action_b}"
+        );
+    }
+
+    println!();
+    println!("{:<24}{:>12}{:>12}", "", "A", "B");
+    let row = |name: &str, a: String, b: String| {
+        println!("{name:<24}{a:>12}{b:>12}");
+    };
+    row(
+        "Ticks",
+        summary_a.last_tick.to_string(),
+        summary_b.last_tick.to_string(),
+    );
+    row("Win tick", summary_a.win_text(), summary_b.win_text());
+    for team in [Team::Player, Team::Enemy] {
+        row(
+            &format!("{team} metal collected"),
+            summary_a.metal_collected(team).to_string(),
+            summary_b.metal_collected(team).to_string(),
+        );
+        row(
+            &format!("{team} bots built"),
+            summary_a.bots_built(team).to_string(),
+            summary_b.bots_built(team).to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// The tick that `ticks` yields next, or the error it yields instead
+fn peek_tick(
+    ticks: &mut Peekable<impl Iterator<Item = eyre::Result<TickData>>>,
+) -> eyre::Result<Option<u32>> {
+    match ticks.peek() {
+        None => Ok(None),
+        Some(Ok(tick_data)) => Ok(Some(tick_data.tick)),
+        Some(Err(_)) => Err(ticks.next().unwrap().unwrap_err()),
+    }
+}
+
+/// The outcome of one replay
+struct Summary {
+    win_condition: WinCondition,
+    last_tick: u32,
+    won: Option<(u32, Team)>,
+    /// Team of every bot that was ever in the match
+    bots: HashMap<BotId, Team>,
+    /// Bots of each team in the first recorded tick, which the level
+    /// started with rather than built
+    initial_bots: Option<HashMap<Team, usize>>,
+    metal_held: HashMap<Team, u32>,
+    metal_collected: HashMap<Team, u32>,
+}
+
+impl Summary {
+    fn new(win_condition: WinCondition) -> Self {
+        Summary {
+            win_condition,
+            last_tick: 0,
+            won: None,
+            bots: HashMap::default(),
+            initial_bots: None,
+            metal_held: HashMap::default(),
+            metal_collected: HashMap::default(),
+        }
+    }
+
+    fn add(&mut self, tick_data: &TickData) {
+        self.initial_bots.get_or_insert_with(|| {
+            let mut initial_bots = HashMap::default();
+            for bot in tick_data.bot_data.values() {
+                *initial_bots.entry(bot.bot_data.team).or_default() += 1;
+            }
+            initial_bots
+        });
+        self.last_tick = tick_data.tick;
+
+        let mut metal_held = HashMap::<Team, u32>::default();
+        for (bot_id, bot) in &tick_data.bot_data {
+            let team = bot.bot_data.team;
+            self.bots.insert(*bot_id, team);
+            *metal_held.entry(team).or_default() +=
+                bot.bot_data.inventory.get(Item::Metal) as u32;
+            if self.won.is_none() && self.win_condition.is_met(&bot.bot_data) {
+                self.won = Some((tick_data.tick, team));
+            }
+        }
+
+        // Metal passed between bots of a team cancels out, so only what the
+        // team gained counts as collected
+        for (team, held) in &metal_held {
+            let before = self.metal_held.get(team).copied().unwrap_or(0);
+            *self.metal_collected.entry(*team).or_default() +=
+                held.saturating_sub(before);
+        }
+        self.metal_held = metal_held;
+    }
+
+    fn metal_collected(&self, team: Team) -> u32 {
+        self.metal_collected.get(&team).copied().unwrap_or(0)
+    }
+
+    fn bots_built(&self, team: Team) -> usize {
+        let bots = self.bots.values().filter(|bot_team| **bot_team == team);
+        let initial_bots = self
+            .initial_bots
+            .as_ref()
+            .and_then(|initial_bots| initial_bots.get(&team))
+            .copied()
+            .unwrap_or(0);
+        bots.count().saturating_sub(initial_bots)
+    }
+
+    fn win_text(&self) -> String {
+        match self.won {
+            Some((tick, team)) => format!("{tick} ({team})"),
+            None => "-".to_string(),
+        }
+    }
+}
+
+fn tick_checksum(tick_data: &TickData) -> u64 {
+    checksum(&tick_data.grid_world, tick_bots(tick_data))
+}
+
+/// The bots of a recorded tick with their entity in the recording
+fn tick_bots(
+    tick_data: &TickData,
+) -> impl Iterator<Item = (Entity, BotId, &BotData)> {
+    tick_data.bot_data.iter().filter_map(|(bot_id, bot)| {
+        let cell = tick_data.grid_world.get(bot.bot_data.pos);
+        Some((cell.pawn?, *bot_id, &bot.bot_data))
+    })
+}
+
+/// Bots whose current action differs between two recordings of the same
+/// tick
+fn action_diffs_at(a: &TickData, b: &TickData) -> Vec<(BotId, String, String)> {
+    let describe = |tick_data: &TickData, bot_id: &BotId| match tick_data
+        .bot_data
+        .get(bot_id)
+    {
+        None => "not there".to_string(),
+        Some(bot) => match &bot.current_action.0 {
+            None => "idle".to_string(),
+            Some(action) => format!("{:?}", action.kind),
+        },
+    };
+
+    let mut bot_ids = a
+        .bot_data
+        .keys()
+        .chain(b.bot_data.keys())
+        .collect::<Vec<_>>();
+    bot_ids.sort_by_key(|bot_id| bot_id.0);
+    bot_ids.dedup();
+    bot_ids
+        .into_iter()
+        .filter_map(|bot_id| {
+            let action_a = describe(a, bot_id);
+            let action_b = describe(b, bot_id);
+            (action_a != action_b).then_some((*bot_id, action_a, action_b))
+        })
+        .collect()
+}
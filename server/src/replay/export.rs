@@ -0,0 +1,268 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use argh::FromArgValue;
+use bevy::utils::HashMap;
+use eyre::WrapErr;
+use serde::Serialize;
+use swarm_lib::{Action, ActionResult, ActionStatus, Inventory, Item, Team};
+
+use super::{load_replay, TickData};
+use crate::game::bot_update::BotId;
+
+/// File format of `--export`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line
+    #[default]
+    JsonLines,
+    Csv,
+}
+
+impl FromArgValue for ExportFormat {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        match value {
+            "jsonl" => Ok(ExportFormat::JsonLines),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!(
+                "Invalid export format: {value}. Expected jsonl or csv"
+            )),
+        }
+    }
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// One bot at one tick
+#[derive(Serialize)]
+struct BotRow {
+    tick: u32,
+    bot_id: u32,
+    team: Team,
+    x: usize,
+    y: usize,
+    energy: u32,
+    crumb: u8,
+    fent: u8,
+    truffle: u8,
+    metal: u8,
+    /// Empty if the bot is idle
+    action: String,
+    /// Reasons of the actions that failed this tick, separated by `; `
+    failures: String,
+}
+
+/// Totals of one team at one tick
+#[derive(Serialize)]
+struct TeamRow {
+    tick: u32,
+    team: Team,
+    bots: u32,
+    energy: u32,
+    crumb: u32,
+    fent: u32,
+    truffle: u32,
+    metal: u32,
+}
+
+/// A build, transfer or message that succeeded
+#[derive(Serialize)]
+struct EventRow {
+    tick: u32,
+    bot_id: u32,
+    team: Team,
+    kind: &'static str,
+    detail: String,
+}
+
+/// Writes the rows of a replay to `bots`, `teams` and `events` files in
+/// `dir`, for loading into notebooks
+pub fn export_replay(
+    replay_path: &str,
+    dir: &str,
+    format: ExportFormat,
+) -> eyre::Result<()> {
+    let mut replay = load_replay(replay_path)?;
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir)
+        .wrap_err_with(|| format!("Could not create {}", dir.display()))?;
+
+    let mut bots = RowWriter::create(dir, "bots", format)?;
+    let mut teams = RowWriter::create(dir, "teams", format)?;
+    let mut events = RowWriter::create(dir, "events", format)?;
+
+    // Number of past actions already exported for each bot
+    let mut past_actions = HashMap::<BotId, usize>::default();
+    for tick_data in replay.ticks() {
+        let tick_data = tick_data?;
+        let mut team_rows = HashMap::<Team, TeamRow>::default();
+
+        let mut bot_ids =
+            tick_data.bot_data.keys().copied().collect::<Vec<_>>();
+        bot_ids.sort_by_key(|bot_id| bot_id.0);
+        for bot_id in bot_ids {
+            let bot = &tick_data.bot_data[&bot_id];
+            let bot_data = &bot.bot_data;
+            let seen = past_actions.insert(bot_id, bot.past_actions.len());
+            let new_actions = bot
+                .past_actions
+                .get(seen.unwrap_or(0)..)
+                .unwrap_or_default();
+
+            bots.write(&BotRow {
+                tick: tick_data.tick,
+                bot_id: bot_id.0,
+                team: bot_data.team,
+                x: bot_data.pos.x(),
+                y: bot_data.pos.y(),
+                energy: bot_data.energy.0,
+                crumb: bot_data.inventory.get(Item::Crumb),
+                fent: bot_data.inventory.get(Item::Fent),
+                truffle: bot_data.inventory.get(Item::Truffle),
+                metal: bot_data.inventory.get(Item::Metal),
+                action: bot
+                    .current_action
+                    .as_ref()
+                    .map(|action| format!("{:?}", action.kind))
+                    .unwrap_or_default(),
+                failures: failures(new_actions),
+            })?;
+
+            for action in new_actions {
+                if let Some(event) =
+                    event_row(&tick_data, bot_id, bot_data.team, action)
+                {
+                    events.write(&event)?;
+                }
+            }
+
+            let team_row =
+                team_rows.entry(bot_data.team).or_insert_with(|| TeamRow {
+                    tick: tick_data.tick,
+                    team: bot_data.team,
+                    bots: 0,
+                    energy: 0,
+                    crumb: 0,
+                    fent: 0,
+                    truffle: 0,
+                    metal: 0,
+                });
+            team_row.add(bot_data.energy.0, &bot_data.inventory);
+        }
+
+        for team in [Team::Player, Team::Enemy] {
+            if let Some(team_row) = team_rows.remove(&team) {
+                teams.write(&team_row)?;
+            }
+        }
+    }
+
+    bots.finish()?;
+    teams.finish()?;
+    events.finish()?;
+    Ok(())
+}
+
+impl TeamRow {
+    fn add(&mut self, energy: u32, inventory: &Inventory) {
+        self.bots += 1;
+        self.energy += energy;
+        self.crumb += inventory.get(Item::Crumb) as u32;
+        self.fent += inventory.get(Item::Fent) as u32;
+        self.truffle += inventory.get(Item::Truffle) as u32;
+        self.metal += inventory.get(Item::Metal) as u32;
+    }
+}
+
+fn failures(actions: &[ActionResult]) -> String {
+    actions
+        .iter()
+        .filter_map(|action| match &action.status {
+            ActionStatus::Failure(reason) => Some(reason.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn event_row(
+    tick_data: &TickData,
+    bot_id: BotId,
+    team: Team,
+    action: &ActionResult,
+) -> Option<EventRow> {
+    if !action.status.is_success() {
+        return None;
+    }
+    let (kind, detail) = match &action.action {
+        Action::Build(dir, frame, _) => ("build", format!("{frame:?} {dir:?}")),
+        Action::Transfer((item, dir)) => {
+            ("transfer", format!("{item:?} {dir:?}"))
+        }
+        Action::Msg { msg, to } => {
+            ("message", format!("{} bytes to {to}", msg.len()))
+        }
+        _ => return None,
+    };
+    Some(EventRow {
+        tick: tick_data.tick,
+        bot_id: bot_id.0,
+        team,
+        kind,
+        detail,
+    })
+}
+
+/// Writes rows as JSON lines, or as CSV with a header taken from the field
+/// names of the row struct, in the order they are declared
+enum RowWriter {
+    JsonLines(BufWriter<File>),
+    Csv(csv::Writer<File>),
+}
+
+impl RowWriter {
+    fn create(
+        dir: &Path,
+        name: &str,
+        format: ExportFormat,
+    ) -> eyre::Result<Self> {
+        let path = dir.join(format!("{name}.{}", format.extension()));
+        let file = File::create(&path)
+            .wrap_err_with(|| format!("Could not create {}", path.display()))?;
+        Ok(match format {
+            ExportFormat::JsonLines => {
+                RowWriter::JsonLines(BufWriter::new(file))
+            }
+            ExportFormat::Csv => RowWriter::Csv(csv::Writer::from_writer(file)),
+        })
+    }
+
+    fn write(&mut self, row: &impl Serialize) -> eyre::Result<()> {
+        match self {
+            RowWriter::JsonLines(file) => {
+                serde_json::to_writer(&mut *file, row)?;
+                writeln!(file)?;
+            }
+            RowWriter::Csv(writer) => writer.serialize(row)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> eyre::Result<()> {
+        match self {
+            RowWriter::JsonLines(mut file) => file.flush()?,
+            RowWriter::Csv(mut writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
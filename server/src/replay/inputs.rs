@@ -0,0 +1,223 @@
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use serde::{Deserialize, Serialize};
+use swarm_lib::BotData;
+
+use crate::{
+    game::{
+        apply_actions::{ActionContainer, CurrentAction, PastActions},
+        bot_lib::fnv1a,
+        bot_update::{
+            submit_action,
+            ActionSkipped,
+            ActionSubmitted,
+            BotId,
+            BotIdToEntity,
+        },
+        time_budget::BotTimeStats,
+    },
+    types::{GridWorld, Tick},
+    TickSpeed,
+};
+
+/// Input replays record a checksum of the state every this many ticks
+pub const CHECKSUM_INTERVAL: u32 = 10;
+
+/// One tick of an input replay
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputRecord {
+    pub tick: u32,
+    /// The actions the bots submitted this tick, in `BotId` order
+    pub actions: Vec<(BotId, ActionContainer)>,
+    /// Bots whose action was thrown away for going over the time budget.
+    /// Re-simulation skips them the same way instead of timing anything.
+    pub skipped: Vec<BotId>,
+    /// [`state_checksum`] at the end of the tick, every
+    /// [`CHECKSUM_INTERVAL`] ticks
+    pub checksum: Option<u64>,
+}
+
+/// An input replay that is being re-simulated
+#[derive(Resource)]
+pub struct ReplayInputs {
+    /// Sorted by tick
+    pub records: Vec<InputRecord>,
+    /// Number of checksums compared so far
+    pub checked: u32,
+    /// Number of checksums that didn't match the re-simulated state
+    pub mismatched: u32,
+}
+
+impl ReplayInputs {
+    fn get(&self, tick: u32) -> Option<&InputRecord> {
+        let idx = self
+            .records
+            .binary_search_by_key(&tick, |record| record.tick)
+            .ok()?;
+        Some(&self.records[idx])
+    }
+
+    fn last_tick(&self) -> u32 {
+        self.records.last().map_or(0, |record| record.tick)
+    }
+}
+
+/// [`checksum`] of the live world
+pub fn state_checksum(
+    grid_world: &GridWorld,
+    bots: &Query<(Entity, &BotId, &BotData)>,
+) -> u64 {
+    checksum(
+        grid_world,
+        bots.iter()
+            .map(|(entity, bot_id, bot_data)| (entity, *bot_id, bot_data)),
+    )
+}
+
+/// Hash of the simulated state. Entities are replaced by `BotId`s, because
+/// their indices depend on everything else that was spawned, like the UI.
+pub fn checksum<'a>(
+    grid_world: &GridWorld,
+    bots: impl IntoIterator<Item = (Entity, BotId, &'a BotData)>,
+) -> u64 {
+    let mut bots = bots.into_iter().collect::<Vec<_>>();
+    bots.sort_by_key(|(_, bot_id, _)| bot_id.0);
+
+    let bot_ids = bots
+        .iter()
+        .map(|(entity, bot_id, _)| (*entity, *bot_id))
+        .collect::<EntityHashMap<_>>();
+
+    let cells = grid_world
+        .iter()
+        .map(|(pos, cell)| {
+            (
+                pos,
+                cell.kind,
+                cell.item,
+                cell.pawn.and_then(|pawn| bot_ids.get(&pawn).copied()),
+                cell.partially_built_bot.is_some(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let bots = bots
+        .iter()
+        .map(|(_, bot_id, bot_data)| {
+            (
+                *bot_id,
+                &bot_data.frame,
+                &bot_data.subsystems,
+                bot_data.energy,
+                &bot_data.inventory,
+                &bot_data.msg_buffer,
+                bot_data.pos,
+                bot_data.team,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let bytes = bincode::serde::encode_to_vec(
+        (cells, bots),
+        bincode::config::standard(),
+    )
+    .unwrap();
+    fnv1a(&bytes)
+}
+
+/// Records the actions the bots submitted this tick
+pub(super) fn extract_inputs(
+    tick: Res<Tick>,
+    mut submitted: EventReader<ActionSubmitted>,
+    mut skipped: EventReader<ActionSkipped>,
+    grid_world: Res<GridWorld>,
+    bots: Query<(Entity, &BotId, &BotData)>,
+) -> InputRecord {
+    InputRecord {
+        tick: tick.0,
+        actions: submitted
+            .read()
+            .map(|submitted| (submitted.bot_id, submitted.action.clone()))
+            .collect(),
+        skipped: skipped.read().map(|skipped| skipped.bot_id).collect(),
+        checksum: (tick.0 % CHECKSUM_INTERVAL == 0)
+            .then(|| state_checksum(&grid_world, &bots)),
+    }
+}
+
+/// Hands the bots their recorded actions in place of `update_bots`
+pub(super) fn apply_replay_inputs(
+    tick: Res<Tick>,
+    inputs: Res<ReplayInputs>,
+    bot_id_to_entity: Res<BotIdToEntity>,
+    mut bots: Query<(&mut CurrentAction, &mut PastActions)>,
+    mut time_stats: Query<&mut BotTimeStats>,
+) {
+    let Some(record) = inputs.get(tick.0) else {
+        return;
+    };
+    for bot_id in &record.skipped {
+        debug!(?bot_id, tick = tick.0, "Skipping action like the recording");
+        let entity = bot_id_to_entity.0.get(bot_id);
+        if let Some(mut time_stats) =
+            entity.and_then(|entity| time_stats.get_mut(*entity).ok())
+        {
+            time_stats.overruns += 1;
+        }
+    }
+    for (bot_id, action) in &record.actions {
+        let Some(entity) = bot_id_to_entity.0.get(bot_id) else {
+            warn!(?bot_id, tick = tick.0, "Recorded action for unknown bot");
+            continue;
+        };
+        let Ok((mut current_action, mut past_actions)) = bots.get_mut(*entity)
+        else {
+            continue;
+        };
+        submit_action(
+            &mut current_action,
+            &mut past_actions,
+            action.clone(),
+            tick.0,
+        );
+    }
+}
+
+/// Compares the re-simulated state against the recorded checksums, and
+/// pauses once the recording runs out
+pub(super) fn verify_checksums(
+    tick: Res<Tick>,
+    mut inputs: ResMut<ReplayInputs>,
+    grid_world: Res<GridWorld>,
+    bots: Query<(Entity, &BotId, &BotData)>,
+    mut tick_speed: ResMut<TickSpeed>,
+) {
+    if let Some(expected) =
+        inputs.get(tick.0).and_then(|record| record.checksum)
+    {
+        let actual = state_checksum(&grid_world, &bots);
+        inputs.checked += 1;
+        if actual != expected {
+            inputs.mismatched += 1;
+            error!(
+                tick = tick.0,
+                expected, actual, "Re-simulation diverged from the recording"
+            );
+        }
+    }
+
+    if tick.0 != inputs.last_tick() {
+        return;
+    }
+    tick_speed.is_paused = true;
+    if inputs.mismatched == 0 {
+        info!(
+            "Re-simulated {} ticks, all {} checksums matched",
+            tick.0, inputs.checked
+        );
+    } else {
+        error!(
+            "Re-simulated {} ticks, {} of {} checksums didn't match",
+            tick.0, inputs.mismatched, inputs.checked
+        );
+    }
+}
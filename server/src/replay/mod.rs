@@ -0,0 +1,867 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{
+        BufRead,
+        BufReader,
+        BufWriter,
+        ErrorKind,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*, utils::HashMap};
+use branch::{branch_from_replay, resume_live};
+pub use debug_bot::debug_bot_update;
+use delta::{ReplayRecord, TickDelta, KEYFRAME_INTERVAL};
+pub use diff::diff_replays;
+pub use export::{export_replay, ExportFormat};
+use eyre::{bail, eyre, WrapErr};
+use inputs::{
+    apply_replay_inputs,
+    extract_inputs,
+    verify_checksums,
+    InputRecord,
+    ReplayInputs,
+};
+use reader::{ReplayIndex, ReplayReader};
+pub use render::render_replay;
+use serde::{Deserialize, Serialize};
+use swarm_lib::{BotData, MatchInfo, Rules};
+use timeline::TimelineBuilder;
+pub use timeline::{ReplayEvent, ReplayTimeline};
+
+use crate::{
+    game::{
+        apply_actions::{CurrentAction, PastActions},
+        bot_lib::{BotLib, BotLibIdentity, MatchSetup},
+        bot_update::{
+            BotCrashes,
+            BotId,
+            BotIdToEntity,
+            BotLogs,
+            BotUpdateSystemSet,
+        },
+        time_budget::{BotTimeStats, TeamTimeStats},
+    },
+    graphics::tilemap::MapSize,
+    levels::LevelArgs,
+    types::*,
+    GameState,
+    WinCondition,
+};
+
+mod branch;
+mod debug_bot;
+mod delta;
+mod diff;
+mod export;
+mod inputs;
+mod reader;
+mod render;
+mod timeline;
+
+/// First bytes of every replay file
+const REPLAY_MAGIC: [u8; 8] = *b"SWRMRPLY";
+
+/// Bump whenever `ReplayHeader` or `ReplayRecord` change shape after a
+/// release, so that replays written by an older build are refused
+const REPLAY_FORMAT_VERSION: u32 = 1;
+
+/// Where the offset of a snapshot replay's index is written, right after the
+/// format version. It is 0 until the match ends and the index is written.
+const INDEX_OFFSET_POSITION: u64 = REPLAY_MAGIC.len() as u64 + 4;
+
+#[derive(Resource)]
+struct Replay {
+    /// Only `None` for a live match that hasn't started yet
+    header: Option<ReplayHeader>,
+    /// The snapshot replay being played back. `None` for a live match, whose
+    /// records are only written to the file
+    reader: Option<ReplayReader>,
+    /// The most recently recorded or restored tick
+    current: Option<TickData>,
+}
+
+impl Replay {
+    fn reader(&mut self) -> &mut ReplayReader {
+        self.reader
+            .as_mut()
+            .expect("Only replays that are played back are read")
+    }
+
+    /// Every recorded tick in order, each rebuilt from the one before
+    fn ticks(&mut self) -> impl Iterator<Item = eyre::Result<TickData>> + '_ {
+        self.reader().ticks()
+    }
+
+    /// Rebuilds the state at the last recorded tick at or before `tick`
+    fn tick_data(
+        &mut self,
+        tick: u32,
+        cached: Option<TickData>,
+    ) -> eyre::Result<Option<TickData>> {
+        self.reader().tick_data(tick, cached)
+    }
+}
+
+/// Written once at the start of a replay file, after the magic number, the
+/// format version and the offset of the index
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub kind: ReplayKind,
+    pub level: LevelArgs,
+    pub seed: u64,
+    pub bot_libs: Vec<BotLibIdentity>,
+    /// From `--bot-param`, so that the bots are created the same way when
+    /// the replay is re-simulated or branched from
+    pub bot_params: BTreeMap<String, String>,
+    /// Recorded so that playing back or comparing the replay doesn't depend
+    /// on the level still being built the same way
+    pub win_condition: WinCondition,
+    /// What the bots were told about the level, for bots that run again
+    /// when branching from the replay or debugging one of its ticks
+    pub match_info: MatchInfo,
+    /// What the bots were told the rules are, including the time budget
+    pub rules: Rules,
+    /// Seconds since the unix epoch
+    pub started_at: u64,
+}
+
+/// What the records after the header hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayKind {
+    /// The full state at every tick, as keyframes and deltas
+    Snapshots,
+    /// Only the actions the bots submitted, to be re-simulated from the
+    /// level and seed
+    Inputs,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TickData {
+    tick: u32,
+    bot_data: HashMap<BotId, BotComponents>,
+    grid_world: GridWorld,
+    partially_built_bots: EntityHashMap<PartiallyBuiltBot>,
+    team_time_stats: TeamTimeStats,
+}
+
+#[derive(Clone, Serialize, Deserialize, Bundle)]
+struct BotComponents {
+    bot_data: BotData,
+    current_action: CurrentAction,
+    past_actions: PastActions,
+    bot_logs: BotLogs,
+    bot_crashes: BotCrashes,
+    bot_time_stats: BotTimeStats,
+}
+
+pub struct ReplayPlugin {
+    pub save_replay: Option<String>,
+    pub record_replay: bool,
+    pub input_replay: bool,
+    pub load_replay: Option<String>,
+}
+
+/// Where live matches are recorded
+#[derive(Resource)]
+struct ReplayOutput {
+    kind: ReplayKind,
+    /// Set by `--save-replay`, otherwise each match gets a timestamped file
+    /// in `replays/`
+    configured: Option<PathBuf>,
+    /// File of the match in progress. `None` if it couldn't be created, or
+    /// once the match has ended
+    current: Option<PathBuf>,
+    /// Tick and offset of every keyframe recorded so far
+    keyframes: Vec<(u32, u64)>,
+    timeline: TimelineBuilder,
+}
+
+#[derive(States, Hash, Eq, PartialEq, Clone, Debug)]
+pub enum LiveOrReplay {
+    Replay,
+    Live,
+    /// Playing an input replay by running the simulation on the recorded
+    /// actions
+    Resimulate,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct ReplaySystemSet;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let header = self.load_replay.as_ref().map(|path| {
+            read_replay_header(path).unwrap_or_else(|err| {
+                panic!("Could not load replay {path}: {err:#}")
+            })
+        });
+        match (&self.load_replay, header) {
+            (
+                Some(load_replay_file),
+                Some(
+                    header @ ReplayHeader {
+                        kind: ReplayKind::Snapshots,
+                        ..
+                    },
+                ),
+            ) => {
+                let mut replay =
+                    load_replay(load_replay_file).unwrap_or_else(|err| {
+                        panic!(
+                            "Could not load replay {load_replay_file}: {err:#}"
+                        )
+                    });
+                app.insert_state(LiveOrReplay::Replay);
+                app.add_systems(
+                    OnEnter(GameState::Idle),
+                    |mut next_state: ResMut<NextState<GameState>>| {
+                        next_state.set(GameState::InGame);
+                    },
+                );
+                let win_condition = header.win_condition.clone();
+                // Start at the first recorded tick so that the bots don't
+                // start at 0
+                let timeline = replay.reader().timeline().clone();
+                let first_tick = replay.reader().first_tick();
+                let first_keyframe = replay
+                    .tick_data(first_tick, None)
+                    .and_then(|tick_data| {
+                        tick_data.ok_or_else(|| eyre!("No first keyframe"))
+                    })
+                    .unwrap_or_else(|err| {
+                        panic!(
+                            "Could not load replay {load_replay_file}: {err:#}"
+                        )
+                    });
+                app.insert_resource(Tick(timeline.first_tick));
+                app.insert_resource(timeline);
+                app.insert_resource(win_condition);
+                app.insert_resource(header.match_info.clone());
+                let grid_world = &first_keyframe.grid_world;
+                app.insert_resource(MapSize {
+                    x: grid_world.width() as u32,
+                    y: grid_world.height() as u32,
+                });
+                app.insert_resource(grid_world.clone());
+                app.insert_resource(replay);
+            }
+            (Some(load_replay_file), Some(header)) => {
+                // The level and seed from the header are picked up in `main`,
+                // so the level sets the match up as it was recorded
+                let inputs = load_input_replay(load_replay_file)
+                    .unwrap_or_else(|err| {
+                        panic!(
+                            "Could not load replay {load_replay_file}: {err:#}"
+                        )
+                    });
+                app.insert_state(LiveOrReplay::Resimulate);
+                app.insert_resource(Replay {
+                    header: Some(header),
+                    reader: None,
+                    current: None,
+                });
+                app.insert_resource(inputs);
+                app.add_systems(
+                    Update,
+                    (
+                        apply_replay_inputs.in_set(BotUpdateSystemSet),
+                        verify_checksums.in_set(ReplaySystemSet),
+                    )
+                        .run_if(in_state(LiveOrReplay::Resimulate)),
+                );
+            }
+            _ => {
+                app.insert_resource(Replay {
+                    header: None,
+                    reader: None,
+                    current: None,
+                });
+                app.insert_state(LiveOrReplay::Live);
+
+                if self.record_replay {
+                    let kind = if self.input_replay {
+                        ReplayKind::Inputs
+                    } else {
+                        ReplayKind::Snapshots
+                    };
+                    app.insert_resource(ReplayOutput {
+                        kind,
+                        configured: self
+                            .save_replay
+                            .as_ref()
+                            .map(PathBuf::from),
+                        current: None,
+                        keyframes: Vec::new(),
+                        timeline: TimelineBuilder::default(),
+                    });
+                    app.add_systems(
+                        OnEnter(GameState::InGame),
+                        start_replay_file,
+                    );
+                    app.add_systems(
+                        OnExit(GameState::InGame),
+                        finish_replay_file,
+                    );
+                    app.add_systems(
+                        Last,
+                        finish_replay_file.run_if(
+                            |exit: EventReader<AppExit>| !exit.is_empty(),
+                        ),
+                    );
+                    let record = match kind {
+                        ReplayKind::Snapshots => {
+                            extract_live_data.pipe(save_snapshot).into_configs()
+                        }
+                        ReplayKind::Inputs => extract_inputs
+                            .pipe(save_replay::<InputRecord>)
+                            .into_configs(),
+                    };
+                    app.add_systems(
+                        Update,
+                        record
+                            .in_set(ReplaySystemSet)
+                            .run_if(in_state(LiveOrReplay::Live)),
+                    );
+                }
+            }
+        }
+
+        app.insert_resource(ReplayEntityToLiveEntity(EntityHashMap::default()));
+        app.add_systems(
+            Update,
+            (
+                restore_replay_at_tick
+                    .in_set(ReplaySystemSet)
+                    .run_if(in_state(LiveOrReplay::Replay)),
+                branch_from_replay
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(not(in_state(LiveOrReplay::Live))),
+            ),
+        );
+        for exited in [LiveOrReplay::Replay, LiveOrReplay::Resimulate] {
+            app.add_systems(
+                OnTransition {
+                    exited,
+                    entered: LiveOrReplay::Live,
+                },
+                resume_live,
+            );
+        }
+    }
+}
+
+/// Reads just the header of a replay file
+pub fn read_replay_header(path: &str) -> eyre::Result<ReplayHeader> {
+    let (_, header, _) = open_replay(path)?;
+    Ok(header)
+}
+
+/// Opens a replay file and reads up to the end of the header. Also returns
+/// the offset of the index, which is 0 if the replay has none.
+fn open_replay(
+    path: &str,
+) -> eyre::Result<(BufReader<File>, ReplayHeader, u64)> {
+    let mut file = BufReader::new(File::open(path)?);
+
+    let mut magic = [0; REPLAY_MAGIC.len()];
+    file.read_exact(&mut magic)
+        .wrap_err("File is too short to be a replay")?;
+    if magic != REPLAY_MAGIC {
+        bail!("Not a replay file, or written before replays had a header");
+    }
+
+    let mut version_bytes = [0; 4];
+    file.read_exact(&mut version_bytes)
+        .wrap_err("Replay header is truncated")?;
+    let version = u32::from_le_bytes(version_bytes);
+    if version != REPLAY_FORMAT_VERSION {
+        bail!(
+            "Replay has format version {version}, but this build only reads \
+             version {REPLAY_FORMAT_VERSION}"
+        );
+    }
+
+    let mut index_offset_bytes = [0; 8];
+    file.read_exact(&mut index_offset_bytes)
+        .wrap_err("Replay header is truncated")?;
+    let index_offset = u64::from_le_bytes(index_offset_bytes);
+
+    let header: ReplayHeader = read_record(&mut file)?
+        .ok_or_else(|| eyre!("Replay header is truncated"))?;
+    Ok((file, header, index_offset))
+}
+
+fn load_replay(path: &str) -> eyre::Result<Replay> {
+    let (header, reader) = ReplayReader::open(path)?;
+    info!(?header, "Loading replay");
+    Ok(Replay {
+        header: Some(header),
+        reader: Some(reader),
+        current: None,
+    })
+}
+
+fn load_input_replay(path: &str) -> eyre::Result<ReplayInputs> {
+    let (mut file, header, _) = open_replay(path)?;
+    if header.kind != ReplayKind::Inputs {
+        bail!("Expected an input replay, found {:?}", header.kind);
+    }
+    info!(?header, "Re-simulating input replay");
+
+    let mut records = Vec::<InputRecord>::new();
+    // As with snapshots, a truncated last tick is dropped
+    while let Some(record) = read_record::<InputRecord>(&mut file)
+        .wrap_err_with(|| format!("Bad tick {}", records.len() + 1))?
+    {
+        if let Some(last) = records.last() {
+            if record.tick <= last.tick {
+                bail!(
+                    "Tick {} is recorded after tick {}",
+                    record.tick,
+                    last.tick
+                );
+            }
+        }
+        records.push(record);
+    }
+    if records.is_empty() {
+        bail!("Replay has no ticks");
+    }
+
+    Ok(ReplayInputs {
+        records,
+        checked: 0,
+        mismatched: 0,
+    })
+}
+
+/// Reads one length prefixed bincode record. Returns `None` at the end of the
+/// file or if the record is truncated.
+fn read_record<T: serde::de::DeserializeOwned>(
+    file: &mut impl Read,
+) -> eyre::Result<Option<T>> {
+    let mut len_bytes = [0; 4];
+    let mut bytes = match file.read_exact(&mut len_bytes) {
+        Ok(()) => vec![0; u32::from_le_bytes(len_bytes) as usize],
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    match file.read_exact(&mut bytes) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let (record, _) =
+        bincode::serde::decode_from_slice(&bytes, bincode::config::standard())?;
+    Ok(Some(record))
+}
+
+fn write_record(file: &mut impl Write, record: &impl Serialize) {
+    let bytes =
+        bincode::serde::encode_to_vec(record, bincode::config::standard())
+            .unwrap();
+
+    let len = bytes.len() as u32;
+    file.write_all(&len.to_le_bytes()).unwrap();
+    file.write_all(&bytes).unwrap();
+}
+
+/// Creates the replay file for the match that is starting and writes its
+/// header
+#[allow(clippy::too_many_arguments)]
+fn start_replay_file(
+    mut replay: ResMut<Replay>,
+    mut output: ResMut<ReplayOutput>,
+    level: Res<LevelArgs>,
+    seed: Res<Seed>,
+    bot_lib: Res<BotLib>,
+    setup: Res<MatchSetup>,
+    win_condition: Res<WinCondition>,
+    match_info: Res<MatchInfo>,
+) {
+    let header = ReplayHeader {
+        kind: output.kind,
+        level: level.clone(),
+        seed: seed.0,
+        bot_libs: vec![bot_lib.identity()],
+        bot_params: setup.bot_params.clone(),
+        win_condition: win_condition.clone(),
+        match_info: match_info.clone(),
+        rules: setup.rules.clone(),
+        started_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+    let path = output.configured.clone().unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "replays/replay-{}-{}.bin",
+            header.started_at,
+            std::process::id()
+        ))
+    });
+
+    output.current = match create_replay_file(&path, &header) {
+        Ok(()) => {
+            info!(?header, ?path, "Recording replay");
+            Some(path)
+        }
+        Err(err) => {
+            error!(
+                ?path,
+                "Could not create replay file, not recording: {err:#}"
+            );
+            None
+        }
+    };
+    output.keyframes.clear();
+    output.timeline = TimelineBuilder::default();
+    replay.header = Some(header);
+    replay.current = None;
+}
+
+fn create_replay_file(path: &Path, header: &ReplayHeader) -> eyre::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&REPLAY_MAGIC)?;
+    file.write_all(&REPLAY_FORMAT_VERSION.to_le_bytes())?;
+    file.write_all(&0u64.to_le_bytes())?;
+    write_record(&mut file, header);
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes the index of a snapshot replay after its last record once the
+/// match ends, and stops recording to the file
+fn finish_replay_file(mut output: ResMut<ReplayOutput>) {
+    let Some(path) = output.current.take() else {
+        return;
+    };
+    if output.kind != ReplayKind::Snapshots {
+        return;
+    }
+    let index = ReplayIndex {
+        keyframes: std::mem::take(&mut output.keyframes),
+        timeline: output.timeline.timeline().clone(),
+    };
+    if let Err(err) = write_index(&path, &index) {
+        error!(?path, "Could not write the replay's index: {err:#}");
+    }
+}
+
+fn write_index(path: &Path, index: &ReplayIndex) -> eyre::Result<()> {
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    let offset = file.seek(SeekFrom::End(0))?;
+    let mut writer = BufWriter::new(&mut file);
+    write_record(&mut writer, index);
+    writer.flush()?;
+    drop(writer);
+    // Pointed to only once it is fully written, so that a replay whose index
+    // was cut short is scanned instead
+    file.seek(SeekFrom::Start(INDEX_OFFSET_POSITION))?;
+    file.write_all(&offset.to_le_bytes())?;
+    Ok(())
+}
+
+fn save_replay<T: Serialize>(record: In<T>, output: Res<ReplayOutput>) {
+    if let Some(path) = &output.current {
+        append_record(path, &*record);
+    }
+}
+
+/// Like `save_replay`, but also collects the index written when the match
+/// ends
+fn save_snapshot(
+    record: In<ReplayRecord>,
+    mut output: ResMut<ReplayOutput>,
+    win_condition: Res<WinCondition>,
+) {
+    let output = &mut *output;
+    let Some(path) = &output.current else {
+        return;
+    };
+    let offset = append_record(path, &*record);
+    if record.is_keyframe() {
+        output.keyframes.push((record.tick(), offset));
+    }
+    output.timeline.add(&record, &win_condition);
+}
+
+/// Appends `record` to the replay file and returns the offset it starts at
+fn append_record(path: &Path, record: &impl Serialize) -> u64 {
+    let file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+    let offset = file.metadata().unwrap().len();
+    let mut file = BufWriter::new(file);
+
+    write_record(&mut file, record);
+    file.flush().unwrap();
+    offset
+}
+
+fn extract_live_data(
+    mut replay: ResMut<Replay>,
+    tick: Res<Tick>,
+    bots: Query<(
+        &BotId,
+        &BotData,
+        &CurrentAction,
+        &PastActions,
+        &BotLogs,
+        &BotCrashes,
+        &BotTimeStats,
+    )>,
+    partially_built_bots: Query<(Entity, &PartiallyBuiltBot)>,
+    grid_world: Res<GridWorld>,
+    team_time_stats: Res<TeamTimeStats>,
+) -> ReplayRecord {
+    let bot_data = bots
+        .iter()
+        .map(
+            |(
+                &bot_id,
+                bot_data,
+                current_action,
+                past_actions,
+                bot_logs,
+                bot_crashes,
+                bot_time_stats,
+            )| {
+                (
+                    bot_id,
+                    BotComponents {
+                        bot_data: bot_data.clone(),
+                        current_action: current_action.clone(),
+                        past_actions: past_actions.clone(),
+                        bot_logs: bot_logs.clone(),
+                        bot_crashes: bot_crashes.clone(),
+                        bot_time_stats: bot_time_stats.clone(),
+                    },
+                )
+            },
+        )
+        .collect();
+
+    let partially_built_bots = partially_built_bots
+        .iter()
+        .map(|(replay_entity, partially_built_bot)| {
+            (replay_entity, partially_built_bot.clone())
+        })
+        .collect();
+
+    let tick_data = TickData {
+        tick: tick.0,
+        bot_data,
+        grid_world: grid_world.clone(),
+        partially_built_bots,
+        team_time_stats: team_time_stats.clone(),
+    };
+
+    // Unchanged known map chunks are shared with the previous tick, so the
+    // delta only has to compare pointers to find them
+    let record = match replay.current.take() {
+        Some(prev) if tick.0 % KEYFRAME_INTERVAL != 0 => {
+            ReplayRecord::Delta(TickDelta::new(&prev, &tick_data))
+        }
+        _ => ReplayRecord::Keyframe(tick_data.clone()),
+    };
+    replay.current = Some(tick_data);
+    record
+}
+
+#[derive(Resource)]
+struct ReplayEntityToLiveEntity(pub EntityHashMap<Entity>);
+
+/// Set up game state from replay at a given tick
+fn restore_replay_at_tick(
+    mut commands: Commands,
+    tick: Res<Tick>,
+    mut replay: ResMut<Replay>,
+    bot_id_to_entity: Res<BotIdToEntity>,
+    mut bots: Query<(
+        &mut BotData,
+        &mut CurrentAction,
+        &mut PastActions,
+        &mut BotLogs,
+        &mut BotCrashes,
+        &mut BotTimeStats,
+    )>,
+    mut partially_built_bots: Query<&mut PartiallyBuiltBot>,
+    mut replay_entity_to_live_entity: ResMut<ReplayEntityToLiveEntity>,
+    mut grid_world: ResMut<GridWorld>,
+    mut team_time_stats: ResMut<TeamTimeStats>,
+) {
+    let cached = replay.current.take();
+    let tick_data = match replay.tick_data(tick.0, cached) {
+        Ok(Some(tick_data)) => tick_data,
+        Ok(None) => {
+            warn!("No tick data found for tick {}", tick.0);
+            return;
+        }
+        Err(err) => {
+            error!("Could not read tick {} of the replay: {err:#}", tick.0);
+            return;
+        }
+    };
+    for (bot_id, components) in tick_data.bot_data.iter() {
+        // Look up the entity in the replay. This may or may not be the same as
+        // the entity in *this* bevy world
+        let cell = tick_data.grid_world.get(components.bot_data.pos);
+        let replay_entity = cell.pawn.unwrap();
+
+        if bot_id.0 == 5 {
+            info!(
+                "Replay entity: {}, bot ID: {}, mapped entity: {:?}",
+                replay_entity,
+                bot_id.0,
+                bot_id_to_entity.0.get(bot_id)
+            );
+        }
+
+        // If the entity exists in this bevy world, update the bot data
+        let entity = bot_id_to_entity.0.get(bot_id);
+        if let Some((
+            mut bot_data,
+            mut current_action,
+            mut past_actions,
+            mut bot_logs,
+            mut bot_crashes,
+            mut bot_time_stats,
+        )) = entity.and_then(|entity| bots.get_mut(*entity).ok())
+        {
+            if replay_entity_to_live_entity.0.get(&replay_entity) != entity {
+                info!(
+                    "Replay entity {} should map to live entity {}. Bot ID: \
+                     {}. Tick: {}, replay entity to live entity: {:?}",
+                    replay_entity,
+                    entity.unwrap(),
+                    bot_id.0,
+                    tick.0,
+                    replay_entity_to_live_entity.0
+                );
+                for (replay_entity, live_entity) in
+                    replay_entity_to_live_entity.0.iter()
+                {
+                    info!(
+                        "Replay entity: {}, live entity: {}",
+                        replay_entity, live_entity,
+                    );
+                }
+            }
+            // Make sure the entity in the replay is mapped correctly to the
+            // entity in this bevy world
+            assert_eq!(
+                replay_entity_to_live_entity.0.get(&replay_entity),
+                entity,
+                "Replay entity {} should map to live entity {}. Bot ID: {}. \
+                 Tick: {}, replay entity to live entity: {:?}",
+                replay_entity,
+                entity.unwrap(),
+                bot_id.0,
+                tick.0,
+                replay_entity_to_live_entity.0
+            );
+            *bot_data = components.bot_data.clone();
+            current_action.0 = components.current_action.0.clone();
+            past_actions.0 = components.past_actions.0.clone();
+            bot_logs.0 = components.bot_logs.0.clone();
+            *bot_crashes = components.bot_crashes.clone();
+            *bot_time_stats = components.bot_time_stats.clone();
+            continue;
+        }
+
+        if bot_id.0 == 5 {
+            info!("Bot ID: {}", bot_id.0);
+            // info!("Pos: {:?}", components.bot_data.pos);
+            // info!("Current action: {:?}", components.current_action.0);
+            // info!("Past actions: {:?}", components.past_actions.0);
+            // info!("Bot logs: {:?}", components.bot_logs.0);
+            info!(
+                "Replay entity to live entity: {:?}",
+                replay_entity_to_live_entity.0
+            );
+        }
+
+        // If the entity doesn't exist in this bevy world, spawn a new one
+        let live_entity = commands.spawn(components.clone()).id();
+
+        // Map the replay entity to the live entity
+        if let Some(replaced) = replay_entity_to_live_entity
+            .0
+            .insert(replay_entity, live_entity)
+        {
+            warn!(
+                "Replaced entity {} with entity {} in top loop, for replay \
+                 entity {}",
+                replaced, live_entity, replay_entity
+            );
+        }
+    }
+
+    // Create partially built bots
+    for (replay_entity, partial) in &tick_data.partially_built_bots {
+        // If the entity exists in this bevy world, update the partially built
+        // bot
+        if let Some(live_entity) =
+            replay_entity_to_live_entity.0.get(replay_entity)
+        {
+            *partially_built_bots.get_mut(*live_entity).unwrap() =
+                partial.clone();
+            continue;
+        }
+
+        // If the entity doesn't exist in this bevy world, spawn a new one
+        let live_entity = commands.spawn(partial.clone()).id();
+
+        // Map the replay entity to the live entity
+        if let Some(replaced) = replay_entity_to_live_entity
+            .0
+            .insert(*replay_entity, live_entity)
+        {
+            warn!(
+                "Replaced entity {} with entity {} in bottom loop, for replay \
+                 entity {}",
+                replaced, live_entity, replay_entity
+            );
+        }
+    }
+
+    *team_time_stats = tick_data.team_time_stats.clone();
+
+    // Update the grid world
+    *grid_world = tick_data.grid_world.clone();
+
+    // Resolve entity mappings
+    for (x, y) in tick_data.grid_world.grid.indices_row_major() {
+        let cell = grid_world.get_tuple_mut(x, y);
+
+        if let Some(replay_entity) = cell.pawn {
+            let live_entity = replay_entity_to_live_entity
+                .0
+                .get(&replay_entity)
+                .expect("Should have a live entity for the replay entity");
+            cell.pawn = Some(*live_entity);
+        }
+
+        if let Some(replay_entity) = cell.partially_built_bot {
+            let live_entity = replay_entity_to_live_entity
+                .0
+                .get(&replay_entity)
+                .expect("Should have a live entity for the replay entity");
+            cell.partially_built_bot = Some(*live_entity);
+        }
+    }
+
+    replay.current = Some(tick_data);
+}
@@ -0,0 +1,269 @@
+//! Snapshot replays are read from the file as they are played back instead of
+//! all at once. At the end of each replay is an index with the byte offset of
+//! every keyframe, so that restoring a tick only decodes from the closest
+//! keyframe, and the replay's timeline, so that loading decodes nothing.
+
+use std::{
+    fs::File,
+    io::{BufReader, Seek, SeekFrom},
+};
+
+use bevy::prelude::*;
+use eyre::{bail, eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    delta::ReplayRecord,
+    open_replay,
+    read_record,
+    ReplayHeader,
+    ReplayKind,
+    ReplayTimeline,
+    TickData,
+    TimelineBuilder,
+};
+
+/// Written after the last record of a snapshot replay, and pointed to from
+/// the start of the file
+#[derive(Serialize, Deserialize)]
+pub(super) struct ReplayIndex {
+    /// Tick and offset of every keyframe, sorted by tick
+    pub(super) keyframes: Vec<(u32, u64)>,
+    pub(super) timeline: ReplayTimeline,
+}
+
+/// A snapshot replay opened for playback
+pub(super) struct ReplayReader {
+    file: BufReader<File>,
+    /// Offset of the first record, right after the header
+    start: u64,
+    /// Offset just past the last record, where the index starts
+    end: u64,
+    /// Tick and offset of every keyframe, sorted by tick
+    keyframes: Vec<(u32, u64)>,
+    /// The tick `tick_data` last rebuilt, and the offset of the record after
+    /// it, so that playing forward doesn't go back to the keyframe
+    resume: Option<(u32, u64)>,
+    timeline: ReplayTimeline,
+}
+
+impl ReplayReader {
+    pub(super) fn open(path: &str) -> eyre::Result<(ReplayHeader, Self)> {
+        let (mut file, header, index_offset) = open_replay(path)?;
+        if header.kind != ReplayKind::Snapshots {
+            bail!("Expected a snapshot replay, found {:?}", header.kind);
+        }
+        let start = file.stream_position()?;
+        let len = file.get_ref().metadata()?.len();
+        // A match that was killed before it ended has no index, and its
+        // records run to the end of the file
+        let end = if (start..len).contains(&index_offset) {
+            index_offset
+        } else {
+            len
+        };
+
+        let mut reader = ReplayReader {
+            file,
+            start,
+            end,
+            keyframes: Vec::new(),
+            resume: None,
+            timeline: ReplayTimeline::default(),
+        };
+        match reader.read_index(index_offset) {
+            Ok(index) => {
+                reader.keyframes = index.keyframes;
+                reader.timeline = index.timeline;
+            }
+            Err(err) => {
+                info!("Scanning {path}: {err:#}");
+                reader.scan(&header)?;
+            }
+        }
+        match reader.keyframes.first() {
+            None => bail!("Replay has no ticks"),
+            Some(&(_, offset)) if offset != start => {
+                bail!("Replay doesn't start with a keyframe")
+            }
+            Some(_) => Ok((header, reader)),
+        }
+    }
+
+    pub(super) fn first_tick(&self) -> u32 {
+        self.keyframes[0].0
+    }
+
+    /// The ticks covered by the replay and the events in them
+    pub(super) fn timeline(&self) -> &ReplayTimeline {
+        &self.timeline
+    }
+
+    /// Reads the index written at `offset` when the match ended
+    fn read_index(&mut self, offset: u64) -> eyre::Result<ReplayIndex> {
+        if offset == 0 {
+            bail!("Replay has no index");
+        }
+        if offset != self.end {
+            bail!("Replay index is past the end of the file");
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        let index = read_record::<ReplayIndex>(&mut self.file)
+            .wrap_err("Bad replay index")?
+            .ok_or_else(|| eyre!("Replay index is truncated"))?;
+        let sorted = index
+            .keyframes
+            .windows(2)
+            .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1);
+        let in_range = index
+            .keyframes
+            .iter()
+            .all(|&(_, offset)| (self.start..self.end).contains(&offset));
+        if !sorted || !in_range {
+            bail!("Replay index doesn't match the replay");
+        }
+        Ok(index)
+    }
+
+    /// Finds the keyframes and builds the timeline by decoding every record,
+    /// for replays without an index
+    fn scan(&mut self, header: &ReplayHeader) -> eyre::Result<()> {
+        let mut keyframes = Vec::new();
+        let mut timeline = TimelineBuilder::default();
+        self.file.seek(SeekFrom::Start(self.start))?;
+        loop {
+            let offset = self.file.stream_position()?;
+            if offset >= self.end {
+                break;
+            }
+            let Some(record) = read_record::<ReplayRecord>(&mut self.file)?
+            else {
+                break;
+            };
+            if record.is_keyframe() {
+                keyframes.push((record.tick(), offset));
+            }
+            timeline.add(&record, &header.win_condition);
+        }
+        self.keyframes = keyframes;
+        self.timeline = timeline.timeline().clone();
+        Ok(())
+    }
+
+    /// Every record in order, from the start of the file. A truncated last
+    /// record, left by a match that was killed mid-write, is dropped.
+    pub(super) fn records(
+        &mut self,
+    ) -> impl Iterator<Item = eyre::Result<ReplayRecord>> + '_ {
+        self.resume = None;
+        let mut error = self
+            .file
+            .seek(SeekFrom::Start(self.start))
+            .err()
+            .map(eyre::Report::from);
+        let mut last_tick = None;
+        let mut count = 0;
+        std::iter::from_fn(move || {
+            if let Some(err) = error.take() {
+                return Some(Err(err));
+            }
+            match self.file.stream_position() {
+                Ok(offset) if offset >= self.end => return None,
+                Ok(_) => {}
+                Err(err) => return Some(Err(err.into())),
+            }
+            count += 1;
+            let record = read_record::<ReplayRecord>(&mut self.file)
+                .wrap_err_with(|| format!("Bad tick {count}"));
+            let record = match record {
+                Ok(record) => record?,
+                Err(err) => return Some(Err(err)),
+            };
+            if let Some(last) = last_tick.replace(record.tick()) {
+                if record.tick() <= last {
+                    return Some(Err(eyre!(
+                        "Tick {} is recorded after tick {last}",
+                        record.tick()
+                    )));
+                }
+            }
+            Some(Ok(record))
+        })
+    }
+
+    /// Every recorded tick in order, each rebuilt from the one before
+    pub(super) fn ticks(
+        &mut self,
+    ) -> impl Iterator<Item = eyre::Result<TickData>> + '_ {
+        let mut current = None::<TickData>;
+        self.records().map(move |record| {
+            match record? {
+                ReplayRecord::Keyframe(tick_data) => current = Some(tick_data),
+                ReplayRecord::Delta(delta) => delta.apply(
+                    current
+                        .as_mut()
+                        .ok_or_else(|| eyre!("Replay starts with a delta"))?,
+                ),
+            }
+            Ok(current.clone().expect("Set above"))
+        })
+    }
+
+    /// Rebuilds the state at the last recorded tick at or before `tick`. It
+    /// starts from the closest keyframe, or from `cached` if that is the
+    /// state this reader last rebuilt and no keyframe is closer.
+    pub(super) fn tick_data(
+        &mut self,
+        tick: u32,
+        cached: Option<TickData>,
+    ) -> eyre::Result<Option<TickData>> {
+        let Some(keyframe) = self
+            .keyframes
+            .partition_point(|(keyframe_tick, _)| *keyframe_tick <= tick)
+            .checked_sub(1)
+        else {
+            return Ok(None);
+        };
+        let (keyframe_tick, keyframe_offset) = self.keyframes[keyframe];
+
+        let (mut tick_data, mut offset) = match (cached, self.resume) {
+            (Some(cached), Some((resume_tick, offset)))
+                if cached.tick == resume_tick
+                    && (keyframe_tick..=tick).contains(&cached.tick) =>
+            {
+                (cached, offset)
+            }
+            _ => {
+                self.file.seek(SeekFrom::Start(keyframe_offset))?;
+                match read_record::<ReplayRecord>(&mut self.file)? {
+                    Some(ReplayRecord::Keyframe(tick_data))
+                        if tick_data.tick == keyframe_tick =>
+                    {
+                        (tick_data, self.file.stream_position()?)
+                    }
+                    _ => bail!("Replay index doesn't match the replay"),
+                }
+            }
+        };
+
+        if self.file.stream_position()? != offset {
+            self.file.seek(SeekFrom::Start(offset))?;
+        }
+        while offset < self.end {
+            let Some(record) = read_record::<ReplayRecord>(&mut self.file)?
+            else {
+                break;
+            };
+            if record.tick() > tick {
+                break;
+            }
+            match record {
+                ReplayRecord::Keyframe(keyframe) => tick_data = keyframe,
+                ReplayRecord::Delta(delta) => delta.apply(&mut tick_data),
+            }
+            offset = self.file.stream_position()?;
+        }
+        self.resume = Some((tick_data.tick, offset));
+        Ok(Some(tick_data))
+    }
+}
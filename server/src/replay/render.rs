@@ -0,0 +1,180 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use eyre::{bail, WrapErr};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay,
+    Frame,
+    Rgba,
+    RgbaImage,
+};
+use swarm_lib::{CellKind, Item, Team};
+
+use super::{delta::ReplayRecord, load_replay, TickData};
+use crate::game::bot_update::BotId;
+
+/// How long each tick is shown in a GIF
+const GIF_FRAME_MS: u32 = 100;
+
+const EMPTY: Rgba<u8> = Rgba([40, 40, 40, 255]);
+const BLOCKED: Rgba<u8> = Rgba([110, 100, 90, 255]);
+const UNKNOWN: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const BUILDING: Rgba<u8> = Rgba([200, 200, 200, 255]);
+
+fn item_color(item: Item) -> Rgba<u8> {
+    match item {
+        Item::Crumb => Rgba([200, 170, 90, 255]),
+        Item::Fent => Rgba([230, 60, 230, 255]),
+        Item::Truffle => Rgba([150, 90, 40, 255]),
+        Item::Metal => Rgba([160, 190, 210, 255]),
+    }
+}
+
+fn team_color(team: Team) -> Rgba<u8> {
+    match team {
+        Team::Player => Rgba([60, 120, 240, 255]),
+        Team::Enemy => Rgba([230, 60, 50, 255]),
+    }
+}
+
+/// Draws every tick of a replay without opening a window. Writes a GIF if
+/// `output` ends in `.gif`, otherwise numbered PNG frames into the `output`
+/// directory.
+///
+/// With `fog_of_war_for`, the map and the other bots are drawn as that bot
+/// knew them, and cells it has never seen are black. Ticks before the bot was
+/// built or after it was destroyed are left out.
+pub fn render_replay(
+    replay_path: &str,
+    output: &str,
+    scale: u32,
+    fog_of_war_for: Option<BotId>,
+) -> eyre::Result<()> {
+    if scale == 0 {
+        bail!("The scale must be at least 1");
+    }
+    let mut replay = load_replay(replay_path)?;
+    if let Some(bot_id) = fog_of_war_for {
+        if !has_bot(replay.reader().records(), bot_id)? {
+            bail!("Bot {} isn't in the replay", bot_id.0);
+        }
+    }
+    let output = Path::new(output);
+    let frames = replay.ticks().filter_map(|tick_data| {
+        let tick_data = match tick_data {
+            Ok(tick_data) => tick_data,
+            Err(err) => return Some(Err(err)),
+        };
+        let bot_exists = fog_of_war_for
+            .is_none_or(|bot_id| tick_data.bot_data.contains_key(&bot_id));
+        bot_exists.then(|| {
+            let frame = render_tick(&tick_data, scale, fog_of_war_for);
+            Ok((tick_data.tick, frame))
+        })
+    });
+
+    if output.extension().is_some_and(|ext| ext == "gif") {
+        let file = File::create(output).wrap_err_with(|| {
+            format!("Could not create {}", output.display())
+        })?;
+        let mut encoder = GifEncoder::new(BufWriter::new(file));
+        encoder.set_repeat(Repeat::Infinite)?;
+        for frame in frames {
+            let (_, frame) = frame?;
+            let delay = Delay::from_numer_denom_ms(GIF_FRAME_MS, 1);
+            encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+        }
+    } else {
+        std::fs::create_dir_all(output).wrap_err_with(|| {
+            format!("Could not create {}", output.display())
+        })?;
+        for frame in frames {
+            let (tick, frame) = frame?;
+            let path = output.join(format!("tick-{tick:05}.png"));
+            frame.save(&path).wrap_err_with(|| {
+                format!("Could not write {}", path.display())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the bot is in any of the records, without rebuilding the ticks
+fn has_bot(
+    records: impl IntoIterator<Item = eyre::Result<ReplayRecord>>,
+    bot_id: BotId,
+) -> eyre::Result<bool> {
+    for record in records {
+        let found = match record? {
+            ReplayRecord::Keyframe(tick_data) => {
+                tick_data.bot_data.contains_key(&bot_id)
+            }
+            ReplayRecord::Delta(delta) => delta.new_bots.contains_key(&bot_id),
+        };
+        if found {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Draws one tick, `scale` pixels per cell
+fn render_tick(
+    tick_data: &TickData,
+    scale: u32,
+    fog_of_war_for: Option<BotId>,
+) -> RgbaImage {
+    let grid_world = &tick_data.grid_world;
+    let (width, height) = (grid_world.width(), grid_world.height());
+    let mut image = RgbaImage::new(width as u32 * scale, height as u32 * scale);
+    let known_map = fog_of_war_for
+        .and_then(|bot_id| tick_data.bot_data.get(&bot_id))
+        .map(|bot| &bot.bot_data.known_map);
+
+    let mut fill_cell = |(x, y): (usize, usize), inset: u32, color| {
+        // Flipped so that y points up, like in the window
+        let top = (height - 1 - y) as u32 * scale;
+        let left = x as u32 * scale;
+        for py in top + inset..top + scale - inset {
+            for px in left + inset..left + scale - inset {
+                image.put_pixel(px, py, color);
+            }
+        }
+    };
+
+    for (pos, cell) in grid_world.iter() {
+        let (kind, item) = match known_map {
+            Some(known_map) => {
+                let known = known_map.get_tuple(pos.0, pos.1);
+                (known.kind, known.item)
+            }
+            None => (cell.kind, cell.item),
+        };
+        let terrain = match kind {
+            CellKind::Unknown => UNKNOWN,
+            CellKind::Empty => EMPTY,
+            CellKind::Blocked => BLOCKED,
+        };
+        fill_cell(pos, 0, terrain);
+        if let Some(item) = item {
+            fill_cell(pos, scale / 3, item_color(item));
+        }
+        if cell.partially_built_bot.is_some() && known_map.is_none() {
+            fill_cell(pos, scale / 4, BUILDING);
+        }
+    }
+
+    let bots = tick_data.bot_data.iter().filter_map(|(bot_id, bot)| {
+        let pos = bot.bot_data.pos;
+        let known = match known_map {
+            Some(known_map) => known_map.get(pos).pawn == Some(bot_id.0),
+            None => true,
+        };
+        known.then_some(((pos.x(), pos.y()), bot.bot_data.team))
+    });
+    for (pos, team) in bots {
+        fill_cell(pos, scale / 8, team_color(team));
+    }
+
+    image
+}
@@ -0,0 +1,159 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use swarm_lib::{ActionResult, BotData, Team};
+
+use super::delta::ReplayRecord;
+use crate::{game::bot_update::BotId, WinCondition};
+
+/// Something worth jumping to in a replay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayEvent {
+    BotSpawned(BotId),
+    ActionFailed(BotId),
+    Won(Team),
+}
+
+/// The ticks covered by a replay and the events in them. Built while the
+/// match is recorded and stored in the replay's index, so that seeking
+/// doesn't have to restore every tick and loading doesn't decode them.
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayTimeline {
+    pub first_tick: u32,
+    pub last_tick: u32,
+    /// Sorted by tick
+    pub events: Vec<(u32, ReplayEvent)>,
+    /// A team has won by the ticks scanned so far
+    won: bool,
+}
+
+/// Builds a `ReplayTimeline` one record at a time
+#[derive(Default)]
+pub(super) struct TimelineBuilder {
+    timeline: ReplayTimeline,
+    /// Number of past actions seen so far for each bot
+    past_actions: HashMap<BotId, usize>,
+    /// Whether any record has been added
+    started: bool,
+}
+
+impl TimelineBuilder {
+    pub(super) fn add(
+        &mut self,
+        record: &ReplayRecord,
+        win_condition: &WinCondition,
+    ) {
+        let timeline = &mut self.timeline;
+        let past_actions = &mut self.past_actions;
+        let tick = record.tick();
+        if !self.started {
+            timeline.first_tick = tick;
+        }
+        timeline.last_tick = tick;
+        match record {
+            ReplayRecord::Keyframe(tick_data) => {
+                for (bot_id, bot) in &tick_data.bot_data {
+                    let seen = past_actions.get(bot_id).copied();
+                    // The bots in the first record were there from the start
+                    if seen.is_none() && self.started {
+                        timeline.push(tick, ReplayEvent::BotSpawned(*bot_id));
+                    }
+                    let new_actions = bot.past_actions.get(seen.unwrap_or(0)..);
+                    timeline.scan_bot(
+                        win_condition,
+                        tick,
+                        *bot_id,
+                        &bot.bot_data,
+                        new_actions.unwrap_or_default(),
+                    );
+                    past_actions.insert(*bot_id, bot.past_actions.len());
+                }
+            }
+            ReplayRecord::Delta(delta) => {
+                for (bot_id, bot) in &delta.new_bots {
+                    timeline.push(tick, ReplayEvent::BotSpawned(*bot_id));
+                    timeline.scan_bot(
+                        win_condition,
+                        tick,
+                        *bot_id,
+                        &bot.bot_data,
+                        &bot.past_actions,
+                    );
+                    past_actions.insert(*bot_id, bot.past_actions.len());
+                }
+                for (bot_id, bot) in &delta.bots {
+                    timeline.scan_bot(
+                        win_condition,
+                        tick,
+                        *bot_id,
+                        &bot.bot_data,
+                        &bot.new_past_actions,
+                    );
+                    let seen = past_actions.entry(*bot_id).or_default();
+                    if bot.past_actions_reset {
+                        *seen = 0;
+                    }
+                    *seen += bot.new_past_actions.len();
+                }
+                for bot_id in &delta.removed_bots {
+                    past_actions.remove(bot_id);
+                }
+            }
+        }
+        self.started = true;
+    }
+
+    pub(super) fn timeline(&self) -> &ReplayTimeline {
+        &self.timeline
+    }
+}
+
+impl ReplayTimeline {
+    fn push(&mut self, tick: u32, event: ReplayEvent) {
+        self.events.push((tick, event));
+    }
+
+    fn scan_bot(
+        &mut self,
+        win_condition: &WinCondition,
+        tick: u32,
+        bot_id: BotId,
+        bot_data: &BotData,
+        new_actions: &[ActionResult],
+    ) {
+        if new_actions.iter().any(|action| action.status.is_failure()) {
+            self.push(tick, ReplayEvent::ActionFailed(bot_id));
+        }
+        if !self.won && win_condition.is_met(bot_data) {
+            self.won = true;
+            self.push(tick, ReplayEvent::Won(bot_data.team));
+        }
+    }
+
+    /// The tick of the first event after `tick` that matches `filter`
+    pub fn next_event(
+        &self,
+        tick: u32,
+        filter: impl Fn(&ReplayEvent) -> bool,
+    ) -> Option<u32> {
+        self.events
+            .iter()
+            .find(|(event_tick, event)| *event_tick > tick && filter(event))
+            .map(|(event_tick, _)| *event_tick)
+    }
+
+    /// The tick at `fraction` of the way through the replay
+    pub fn tick_at(&self, fraction: f32) -> u32 {
+        let span = (self.last_tick - self.first_tick) as f32;
+        self.first_tick + (fraction.clamp(0.0, 1.0) * span).round() as u32
+    }
+
+    /// How far through the replay `tick` is, from 0 to 1
+    pub fn fraction(&self, tick: u32) -> f32 {
+        if self.last_tick <= self.first_tick {
+            return 1.0;
+        }
+        let tick = tick.clamp(self.first_tick, self.last_tick);
+        (tick - self.first_tick) as f32
+            / (self.last_tick - self.first_tick) as f32
+    }
+}
@@ -0,0 +1,185 @@
+//! Runs matches on small maps without a window, so that tests can check how
+//! the simulation and the bots behave over a number of ticks.
+//!
+//! The tests need the bots from `simple-bots`, which `cargo test` doesn't
+//! build, so they are ignored by default. Run them with
+//!
+//! ```sh
+//! cargo build -p simple-bots && cargo test -p server -- --ignored
+//! ```
+
+use std::{
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{prelude::*, state::app::StatesPlugin};
+use swarm_lib::{ActionResult, BotData, Team};
+
+use crate::{
+    game::{
+        apply_actions::{ActionsPlugin, PastActions},
+        bot_lib::{rules, BotLib, MatchSetup},
+        bot_update::{BotId, BotUpdatePlugin},
+        core::CorePlugin,
+        time_budget::{BudgetEnforcement, TimeBudget},
+    },
+    levels::MapFile,
+    replay::LiveOrReplay,
+    types::Tick,
+    WinCondition,
+};
+
+mod tests;
+
+/// A match being simulated one tick at a time
+pub struct Scenario {
+    app: App,
+}
+
+impl Scenario {
+    /// Sets up a match on `map`, written like a `load-map` file, with the
+    /// bots from `simple-bots`. Panics if they haven't been built.
+    pub fn new(map: &str) -> Self {
+        Self::with_bot_lib(map, &default_bot_lib())
+    }
+
+    pub fn with_bot_lib(map: &str, bot_lib: &Path) -> Self {
+        let map: MapFile =
+            toml::from_str(map).expect("The scenario map doesn't parse");
+        map.check().expect("The scenario map can't be built");
+        assert!(
+            bot_lib.exists(),
+            "No bot library at {bot_lib:?}. Build it with `cargo build -p \
+             simple-bots` and the same profile as the tests, or point \
+             SWARM_BOT_LIB at one"
+        );
+
+        // Generous, so that a slow debug build doesn't change what the bots
+        // do
+        let time_budget = TimeBudget {
+            per_tick: Duration::from_secs(10),
+            bank: Duration::from_secs(60),
+            enforcement: BudgetEnforcement::Off,
+        };
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            StatesPlugin,
+            ActionsPlugin,
+            CorePlugin,
+            BotUpdatePlugin,
+        ))
+        .insert_state(LiveOrReplay::Live)
+        .insert_resource(BotLib::load(bot_lib))
+        .insert_resource(MatchSetup {
+            rules: rules(&map.win, &time_budget),
+            ..default()
+        })
+        .insert_resource(time_budget)
+        .insert_resource(map.win.clone());
+        app.finish();
+        app.cleanup();
+
+        let world = app.world_mut();
+        map.build(&mut world.commands());
+        world.flush();
+        Scenario { app }
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.app.world().resource::<Tick>().0
+    }
+
+    /// Simulates the next tick
+    pub fn step(&mut self) {
+        self.app.world_mut().resource_mut::<Tick>().0 += 1;
+        self.app.update();
+    }
+
+    /// Simulates ticks until `tick` has been simulated
+    pub fn run_to(&mut self, tick: u32) {
+        while self.tick() < tick {
+            self.step();
+        }
+    }
+
+    /// Simulates ticks until `done` holds, and returns the tick it first
+    /// held at. `None` if it still didn't by `max_tick`.
+    pub fn run_until(
+        &mut self,
+        max_tick: u32,
+        mut done: impl FnMut(&mut Self) -> bool,
+    ) -> Option<u32> {
+        while self.tick() < max_tick {
+            self.step();
+            if done(self) {
+                return Some(self.tick());
+            }
+        }
+        None
+    }
+
+    pub fn world(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    /// Every bot that has been built, in `BotId` order
+    pub fn bots(&mut self) -> Vec<(BotId, BotData)> {
+        let world = self.app.world_mut();
+        let mut bots = world
+            .query::<(&BotId, &BotData)>()
+            .iter(world)
+            .map(|(bot_id, bot_data)| (*bot_id, bot_data.clone()))
+            .collect::<Vec<_>>();
+        bots.sort_by_key(|(bot_id, _)| bot_id.0);
+        bots
+    }
+
+    /// Every action that has failed so far, with the bot that submitted it
+    pub fn failed_actions(&mut self) -> Vec<(BotId, ActionResult)> {
+        let world = self.app.world_mut();
+        let mut failed = world
+            .query::<(&BotId, &PastActions)>()
+            .iter(world)
+            .flat_map(|(bot_id, past_actions)| {
+                past_actions
+                    .iter()
+                    .filter(|action| action.status.is_failure())
+                    .map(|action| (*bot_id, action.clone()))
+            })
+            .collect::<Vec<_>>();
+        failed
+            .sort_by_key(|(bot_id, action)| (action.completed_tick, bot_id.0));
+        failed
+    }
+
+    /// The team of the first bot that holds what the map's win condition
+    /// asks for
+    pub fn winner(&mut self) -> Option<Team> {
+        let win_condition = self.app.world().resource::<WinCondition>().clone();
+        self.bots()
+            .into_iter()
+            .find(|(_, bot_data)| win_condition.is_met(bot_data))
+            .map(|(_, bot_data)| bot_data.team)
+    }
+}
+
+/// `simple-bots` as `cargo build -p simple-bots` builds it, unless
+/// `SWARM_BOT_LIB` points somewhere else.
+///
+/// The test binary is in `deps` under the directory of its profile, so this
+/// follows `CARGO_TARGET_DIR`, `--release` and `--target` like the tests do.
+fn default_bot_lib() -> PathBuf {
+    if let Some(path) = std::env::var_os("SWARM_BOT_LIB") {
+        return path.into();
+    }
+    let test_binary = std::env::current_exe()
+        .expect("The path of the test binary is needed to find the bots");
+    let profile_dir = test_binary
+        .parent()
+        .and_then(Path::parent)
+        .expect("Test binaries are built into a deps directory");
+    profile_dir.join(format!("{DLL_PREFIX}simple_bots{DLL_SUFFIX}"))
+}
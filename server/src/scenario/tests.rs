@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use swarm_lib::{ActionStatus, FrameKind, MatchInfo, Pos, Subsystem, Team};
+
+use super::Scenario;
+use crate::game::bot_update::BotInstance;
+
+/// A base with enough Metal for a gatherer, in a room with more Metal
+const BASE_IN_A_ROOM: &str = r#"
+terrain = """
+############
+#..........#
+#..........#
+#...mmmm...#
+#..........#
+#..........#
+#..........#
+############
+"""
+
+[[spawn]]
+pos = [2, 2]
+team = "Player"
+frame = { Building = "Small" }
+subsystems = { Assembler = 1, CargoBay = 3, PowerCell = 2 }
+inventory = { Metal = 3 }
+"#;
+
+#[test]
+#[ignore = "needs simple-bots, see the scenario module"]
+fn base_builds_a_gatherer() {
+    let mut scenario = Scenario::new(BASE_IN_A_ROOM);
+    let built = scenario.run_until(80, |scenario| {
+        scenario.bots().iter().any(|(_, bot_data)| {
+            bot_data.frame == FrameKind::Flea
+                && bot_data.subsystems.has(Subsystem::CargoBay)
+        })
+    });
+    assert!(built.is_some(), "No gatherer was built by tick 80");
+}
+
+#[test]
+#[ignore = "needs simple-bots, see the scenario module"]
+fn no_action_fails_for_lack_of_energy() {
+    let mut scenario = Scenario::new(BASE_IN_A_ROOM);
+    scenario.run_to(150);
+    let out_of_energy = scenario
+        .failed_actions()
+        .into_iter()
+        .filter(|(_, action)| {
+            action.status == ActionStatus::Failure("Insufficient Energy".into())
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        out_of_energy.is_empty(),
+        "Actions failed for lack of energy: {out_of_energy:#?}"
+    );
+}
+
+#[test]
+#[ignore = "needs simple-bots, see the scenario module"]
+fn bots_never_share_a_cell() {
+    let mut scenario = Scenario::new(BASE_IN_A_ROOM);
+    let shared = scenario.run_until(150, |scenario| {
+        let bots = scenario.bots();
+        let cells = bots
+            .iter()
+            .map(|(_, bot_data)| bot_data.pos.0)
+            .collect::<HashSet<_>>();
+        cells.len() != bots.len()
+    });
+    assert_eq!(shared, None, "Two bots were on the same cell");
+}
+
+#[test]
+#[ignore = "needs simple-bots, see the scenario module"]
+fn map_regions_are_shared_with_bots() {
+    let map = format!(
+        "{BASE_IN_A_ROOM}
+[[region]]
+name = \"mine\"
+kind = \"Zone\"
+from = [7, 4]
+to = [4, 4]
+"
+    );
+    let mut scenario = Scenario::new(&map);
+    let match_info = scenario.world().resource::<MatchInfo>().clone();
+    let mine = match_info.region("mine").expect("The mine wasn't shared");
+    assert_eq!(mine.cells().count(), 4);
+    assert!(mine.contains(Pos((5, 4))));
+}
+
+#[test]
+#[ignore = "needs simple-bots, see the scenario module"]
+fn bots_are_told_the_match_when_created() {
+    let mut scenario = Scenario::new(BASE_IN_A_ROOM);
+    let built = scenario.run_until(80, |scenario| scenario.bots().len() > 1);
+    assert!(built.is_some(), "No bot was built by tick 80");
+
+    let world = scenario.world();
+    let configs = world
+        .query::<&BotInstance>()
+        .iter(world)
+        .map(|instance| instance.config.clone())
+        .collect::<Vec<_>>();
+    for config in &configs {
+        assert_eq!((config.map_width, config.map_height), (12, 8));
+        assert_eq!(config.team, Team::Player);
+        assert!(!config.rules.win_hold.is_empty());
+    }
+    let seeds = configs
+        .iter()
+        .map(|config| config.seed)
+        .collect::<HashSet<_>>();
+    assert_eq!(seeds.len(), configs.len(), "Two bots got the same seed");
+}
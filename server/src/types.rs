@@ -18,6 +18,12 @@ pub type GridWorld = gridworld::GridWorld<CellState>;
 #[derive(Resource, Default)]
 pub struct Tick(pub u32);
 
+/// Seed for everything random in a match, so it can be reproduced
+#[derive(
+    Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct Seed(pub u64);
+
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct PartiallyBuiltBot {
     pub frame_kind: FrameKind,
@@ -67,7 +73,7 @@ impl CellState {
             && self.partially_built_bot.is_none()
     }
 
-    pub fn from_client_state(
+    pub(crate) fn from_client_state(
         state: &ClientCellState,
         bot_id_map: &BotIdToEntity,
     ) -> CellState {
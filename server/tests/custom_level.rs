@@ -0,0 +1,94 @@
+//! A level added from outside the server, the way another crate would
+
+use argh::FromArgs;
+use bevy::prelude::*;
+use server::{
+    CellState,
+    GridWorld,
+    Level,
+    LevelArgs,
+    LevelRegistry,
+    MapReport,
+    MapSize,
+    Seed,
+};
+use swarm_lib::{
+    known_map::{ClientCellState, KnownMap},
+    BotData,
+    Energy,
+    FrameKind,
+    Item,
+    Pos,
+    Subsystem,
+    Subsystems,
+    Team,
+};
+
+struct Corridor;
+
+#[derive(FromArgs)]
+/// A corridor with the Fent at the far end
+struct CorridorArgs {
+    #[argh(option, default = "10")]
+    /// the length of the corridor
+    length: usize,
+}
+
+impl Level for Corridor {
+    const NAME: &'static str = "corridor";
+    const DESCRIPTION: &'static str = "A corridor with the Fent at the far end";
+    type Args = CorridorArgs;
+
+    fn init(
+        args: &CorridorArgs,
+        commands: &mut Commands,
+        _seed: Seed,
+    ) -> eyre::Result<()> {
+        let (width, height) = (args.length, 1);
+        commands.insert_resource(MapSize {
+            x: width as u32,
+            y: height as u32,
+        });
+
+        let mut grid_world = GridWorld::new(width, height, CellState::empty());
+        let player = commands
+            .spawn(BotData::new(
+                FrameKind::default(),
+                Subsystems::new([(Subsystem::CargoBay, 1)]),
+                Pos((0, 0)),
+                Team::Player,
+                Energy(100),
+                KnownMap::new(width, height, ClientCellState::default()),
+                Vec::new(),
+            ))
+            .id();
+        grid_world.set_tuple(0, 0, CellState::new_with_pawn(player));
+        grid_world.get_tuple_mut(width - 1, 0).item = Some(Item::Fent);
+        commands.insert_resource(grid_world);
+        Ok(())
+    }
+
+    fn map_size(args: &CorridorArgs) -> Option<(usize, usize)> {
+        Some((args.length, 1))
+    }
+}
+
+#[test]
+fn registered_level_builds() {
+    let mut levels = LevelRegistry::default();
+    levels.register::<Corridor>();
+    assert!(levels.list().contains("corridor"));
+
+    let level = LevelArgs {
+        name: "corridor".into(),
+        args: vec!["--length".into(), "6".into()],
+    };
+    assert!(levels.check(&level).is_ok());
+    assert_eq!(levels.map_size(&level), Some((6, 1)));
+
+    let mut world = levels.build_detached(&level, Seed(0)).unwrap();
+    let grid_world = world.resource::<GridWorld>();
+    assert_eq!((grid_world.width(), grid_world.height()), (6, 1));
+    let report = MapReport::of_world(&mut world);
+    assert!(report.unreachable_items.is_empty());
+}
@@ -1,7 +1,7 @@
 #![allow(unused_imports, dead_code)]
 
 use rand::{rngs::SmallRng, SeedableRng};
-use swarm_lib::{bot_logger::BotLogger, Bot, NewBotNoMangeFn};
+use swarm_lib::{bot_logger::BotLogger, Bot, BotConfig, NewBotNoMangeFn};
 
 mod econ_bot;
 mod old;
@@ -12,10 +12,10 @@ pub fn test_fn() -> String {
 }
 
 #[no_mangle]
-pub fn new_bot(ctx: BotLogger) -> Box<dyn Bot> {
+pub fn new_bot(ctx: BotLogger, config: BotConfig) -> Box<dyn Bot> {
     Box::new(econ_bot::EconBot {
         role: econ_bot::EconBotRole::default(),
-        rng: SmallRng::seed_from_u64(ctx.bot_id as u64),
+        rng: SmallRng::seed_from_u64(config.seed),
         ctx,
         action_counter: 0,
     })
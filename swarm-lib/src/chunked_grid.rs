@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::gridworld::GridStorage;
+
+/// Side length of a chunk in cells
+pub const CHUNK_SIZE: usize = 16;
+
+/// Grid storage split into square chunks behind reference counting.
+///
+/// Chunks that were never written are not allocated and read as the fill
+/// value. Cloning the grid only clones the chunk pointers, and a chunk is
+/// copied the first time it is written while shared with another grid. This
+/// keeps memory proportional to the cells that differ from the fill value,
+/// no matter how many grids share them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkedGrid<T> {
+    chunks: Vec<Option<Arc<Vec<T>>>>,
+    fill: T,
+    width: usize,
+    height: usize,
+}
+
+impl<T: Clone> ChunkedGrid<T> {
+    fn chunks_y(&self) -> usize {
+        self.height.div_ceil(CHUNK_SIZE)
+    }
+
+    /// Index of the chunk containing `(x, y)` and of the cell in that chunk
+    fn index(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let chunk = (x / CHUNK_SIZE) * self.chunks_y() + y / CHUNK_SIZE;
+        let cell = (x % CHUNK_SIZE) * CHUNK_SIZE + y % CHUNK_SIZE;
+        Some((chunk, cell))
+    }
+
+    /// Shares every chunk of `other` that this grid has not allocated yet,
+    /// and calls `merge` for each cell of chunks both grids have. Chunks that
+    /// are already shared are skipped.
+    pub fn merge_from(
+        &mut self,
+        other: &Self,
+        mut merge: impl FnMut(&mut T, &T),
+    ) {
+        assert_eq!(
+            (self.width, self.height),
+            (other.width, other.height),
+            "Can only merge grids of the same size"
+        );
+        for (ours, theirs) in self.chunks.iter_mut().zip(&other.chunks) {
+            let Some(theirs) = theirs else {
+                continue;
+            };
+            let Some(ours) = ours else {
+                *ours = Some(theirs.clone());
+                continue;
+            };
+            if Arc::ptr_eq(ours, theirs) {
+                continue;
+            }
+            for (our_cell, their_cell) in
+                Arc::make_mut(ours).iter_mut().zip(theirs.iter())
+            {
+                merge(our_cell, their_cell);
+            }
+        }
+    }
+}
+
+/// The chunks that differ between two grids of the same size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkedGridDelta<T> {
+    chunks: Vec<(usize, Option<Arc<Vec<T>>>)>,
+}
+
+impl<T: Clone> ChunkedGrid<T> {
+    /// Chunks of this grid that are not shared with `base`
+    pub fn delta_from(&self, base: &Self) -> ChunkedGridDelta<T> {
+        assert_eq!(
+            self.chunks.len(),
+            base.chunks.len(),
+            "Can only diff grids of the same size"
+        );
+        let chunks = self
+            .chunks
+            .iter()
+            .zip(&base.chunks)
+            .enumerate()
+            .filter(|(_, (ours, base))| match (ours, base) {
+                (Some(ours), Some(base)) => !Arc::ptr_eq(ours, base),
+                (None, None) => false,
+                _ => true,
+            })
+            .map(|(idx, (ours, _))| (idx, ours.clone()))
+            .collect();
+        ChunkedGridDelta { chunks }
+    }
+
+    /// Turns the `base` passed to [`ChunkedGrid::delta_from`] into the grid
+    /// the delta was taken from
+    pub fn apply_delta(&mut self, delta: &ChunkedGridDelta<T>) {
+        for (idx, chunk) in &delta.chunks {
+            self.chunks[*idx] = chunk.clone();
+        }
+    }
+}
+
+impl<T: Clone> GridStorage<T> for ChunkedGrid<T> {
+    fn filled_with(fill: T, width: usize, height: usize) -> Self {
+        let chunks = width.div_ceil(CHUNK_SIZE) * height.div_ceil(CHUNK_SIZE);
+        ChunkedGrid {
+            chunks: vec![None; chunks],
+            fill,
+            width,
+            height,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<&T> {
+        let (chunk, cell) = self.index(x, y)?;
+        match &self.chunks[chunk] {
+            Some(chunk) => Some(&chunk[cell]),
+            None => Some(&self.fill),
+        }
+    }
+
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        let (chunk, cell) = self.index(x, y)?;
+        let fill = &self.fill;
+        let chunk = self.chunks[chunk].get_or_insert_with(|| {
+            Arc::new(vec![fill.clone(); CHUNK_SIZE * CHUNK_SIZE])
+        });
+        Some(&mut Arc::make_mut(chunk)[cell])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_get_mut() {
+        let mut grid = ChunkedGrid::filled_with(0, 20, 40);
+        assert_eq!(grid.get(19, 39), Some(&0));
+        assert_eq!(grid.get(20, 0), None);
+        assert_eq!(grid.get(0, 40), None);
+
+        *grid.get_mut(17, 33).unwrap() = 7;
+        assert_eq!(grid.get(17, 33), Some(&7));
+        assert_eq!(grid.get(17, 32), Some(&0));
+        assert_eq!(grid.chunks.iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn test_clone_copies_on_write() {
+        let mut grid = ChunkedGrid::filled_with(0, 32, 32);
+        *grid.get_mut(1, 1).unwrap() = 1;
+        *grid.get_mut(20, 20).unwrap() = 2;
+
+        let mut copy = grid.clone();
+        *copy.get_mut(1, 2).unwrap() = 3;
+
+        assert_eq!(grid.get(1, 2), Some(&0));
+        assert_eq!(copy.get(1, 1), Some(&1));
+        assert_eq!(copy.get(1, 2), Some(&3));
+        // Only the written chunk was copied
+        assert!(!Arc::ptr_eq(
+            grid.chunks[0].as_ref().unwrap(),
+            copy.chunks[0].as_ref().unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            grid.chunks[3].as_ref().unwrap(),
+            copy.chunks[3].as_ref().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_delta() {
+        let mut base = ChunkedGrid::filled_with(0, 32, 32);
+        *base.get_mut(0, 0).unwrap() = 1;
+        *base.get_mut(20, 0).unwrap() = 2;
+
+        let mut grid = base.clone();
+        *grid.get_mut(0, 1).unwrap() = 3;
+        *grid.get_mut(20, 20).unwrap() = 4;
+
+        let delta = grid.delta_from(&base);
+        assert_eq!(delta.chunks.len(), 2);
+
+        base.apply_delta(&delta);
+        assert_eq!(base.get(0, 0), Some(&1));
+        assert_eq!(base.get(0, 1), Some(&3));
+        assert_eq!(base.get(20, 0), Some(&2));
+        assert_eq!(base.get(20, 20), Some(&4));
+    }
+
+    #[test]
+    fn test_merge_from() {
+        let mut ours = ChunkedGrid::filled_with(0, 32, 32);
+        let mut theirs = ChunkedGrid::filled_with(0, 32, 32);
+        *ours.get_mut(0, 0).unwrap() = 5;
+        *theirs.get_mut(0, 1).unwrap() = 3;
+        *theirs.get_mut(0, 0).unwrap() = 2;
+        *theirs.get_mut(31, 31).unwrap() = 4;
+
+        ours.merge_from(&theirs, |a, b| *a = (*a).max(*b));
+
+        assert_eq!(ours.get(0, 0), Some(&5));
+        assert_eq!(ours.get(0, 1), Some(&3));
+        assert_eq!(ours.get(31, 31), Some(&4));
+        // Chunks we didn't have are shared, not copied
+        assert!(Arc::ptr_eq(
+            ours.chunks[3].as_ref().unwrap(),
+            theirs.chunks[3].as_ref().unwrap()
+        ));
+    }
+}
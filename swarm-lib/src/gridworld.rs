@@ -1,3 +1,5 @@
+use std::marker::PhantomData;
+
 use array2d::Array2D;
 use bevy_ecs::system::Resource;
 use serde::{Deserialize, Serialize};
@@ -5,14 +7,61 @@ use serde::{Deserialize, Serialize};
 use crate::{Dir, Pos};
 
 #[derive(Resource, Debug, Clone, Serialize, Deserialize)]
-pub struct GridWorld<CellState> {
-    pub grid: Array2D<CellState>,
+pub struct GridWorld<CellState, Storage = Array2D<CellState>> {
+    pub grid: Storage,
+    #[serde(skip)]
+    _cell: PhantomData<CellState>,
+}
+
+/// Backing storage of a [`GridWorld`]. `x` ranges over the width and `y`
+/// over the height.
+pub trait GridStorage<CellState> {
+    fn filled_with(fill: CellState, width: usize, height: usize) -> Self;
+
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    fn get(&self, x: usize, y: usize) -> Option<&CellState>;
+
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut CellState>;
+}
+
+impl<CellState: Clone> GridStorage<CellState> for Array2D<CellState> {
+    fn filled_with(fill: CellState, width: usize, height: usize) -> Self {
+        Array2D::filled_with(fill, width, height)
+    }
+
+    fn width(&self) -> usize {
+        self.num_rows()
+    }
+
+    fn height(&self) -> usize {
+        self.num_columns()
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<&CellState> {
+        Array2D::get(self, x, y)
+    }
+
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut CellState> {
+        Array2D::get_mut(self, x, y)
+    }
 }
 
 impl<CellState: PassableCell> GridWorld<CellState> {
     pub fn new(width: usize, height: usize, fill: CellState) -> Self {
+        Self::with_storage(width, height, fill)
+    }
+}
+
+impl<CellState: PassableCell, Storage: GridStorage<CellState>>
+    GridWorld<CellState, Storage>
+{
+    pub fn with_storage(width: usize, height: usize, fill: CellState) -> Self {
         Self {
-            grid: Array2D::filled_with(fill, width, height),
+            grid: Storage::filled_with(fill, width, height),
+            _cell: PhantomData,
         }
     }
 
@@ -63,19 +112,24 @@ impl<CellState: PassableCell> GridWorld<CellState> {
     }
 
     pub fn set_tuple(&mut self, x: usize, y: usize, state: CellState) {
-        let _ = self.grid.set(x, y, state);
+        if let Some(cell) = self.grid.get_mut(x, y) {
+            *cell = state;
+        }
     }
 
     pub fn width(&self) -> usize {
-        self.grid.num_rows()
+        self.grid.width()
     }
 
     pub fn height(&self) -> usize {
-        self.grid.num_columns()
+        self.grid.height()
     }
 
     pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &CellState)> {
-        self.grid.enumerate_row_major()
+        let height = self.height();
+        (0..self.width()).flat_map(move |x| {
+            (0..height).map(move |y| ((x, y), self.get_tuple(x, y)))
+        })
     }
 
     /// Returns cells in ascending order of Manhattan distance from the given
@@ -261,9 +315,9 @@ impl PathFinder {
         path
     }
 
-    pub fn find_path<T: PassableCell>(
+    pub fn find_path<T: PassableCell, S: GridStorage<T>>(
         &mut self,
-        grid: &GridWorld<T>,
+        grid: &GridWorld<T, S>,
         start: Pos,
         goal: Pos,
         use_adjacent: bool,
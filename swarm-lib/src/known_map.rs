@@ -3,6 +3,7 @@ use std::ops::{Deref, DerefMut};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    chunked_grid::ChunkedGrid,
     gridworld::{GridWorld, PassableCell},
     CellKind,
     FrameKind,
@@ -23,9 +24,13 @@ pub struct ClientBotData {
     pub subsystems: Subsystems,
 }
 
+pub type KnownGrid = GridWorld<ClientCellState, ChunkedGrid<ClientCellState>>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KnownMap {
-    pub map: GridWorld<ClientCellState>,
+    /// Chunks are shared with every copy of this map, e.g. the one in a
+    /// `BotUpdate` or the map of a bot built by this one, until written.
+    pub map: KnownGrid,
     pub last_received_map_from: Option<u32>,
 }
 
@@ -40,25 +45,24 @@ pub struct ClientCellState {
 
 impl KnownMap {
     pub fn update_from(&mut self, other: &Self, from: u32) {
-        for (pos, theirs) in other.iter() {
-            let ours = self.get_mut(Pos::from(pos));
+        self.map.grid.merge_from(&other.map.grid, |ours, theirs| {
             if theirs.last_observed > ours.last_observed {
                 *ours = theirs.clone();
             }
-        }
+        });
         self.last_received_map_from = Some(from);
     }
 
     pub fn new(width: usize, height: usize, default: ClientCellState) -> Self {
         Self {
-            map: GridWorld::new(width, height, default),
+            map: GridWorld::with_storage(width, height, default),
             last_received_map_from: None,
         }
     }
 }
 
 impl Deref for KnownMap {
-    type Target = GridWorld<ClientCellState>;
+    type Target = KnownGrid;
 
     fn deref(&self) -> &Self::Target {
         &self.map
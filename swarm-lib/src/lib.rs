@@ -2,32 +2,49 @@
 #![feature(try_trait_v2)]
 // #![feature(generic_const_exprs)]
 
-use std::ops::{ControlFlow, FromResidual, Try};
+use std::{
+    ops::{ControlFlow, FromResidual, Try},
+    sync::Arc,
+};
 
 use bevy_ecs::component::Component;
 pub use bevy_math;
 use bot_logger::{BotLogger, LogEntry};
 
 pub mod bot_logger;
+pub mod chunked_grid;
 pub mod gridworld;
 pub mod known_map;
+pub mod match_info;
 pub mod radar;
 pub mod types;
 
 use known_map::{ClientBotData, KnownMap};
+pub use match_info::*;
 pub use radar::*;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use strum_macros::{Display, EnumCount, EnumDiscriminants, FromRepr};
 pub use types::*;
 use ustr::Ustr;
 
-pub type NewBotNoMangeFn = fn(logger: BotLogger) -> Box<dyn Bot>;
+pub type NewBotNoMangeFn =
+    fn(logger: BotLogger, config: BotConfig) -> Box<dyn Bot>;
 
 pub trait Bot: Sync + Send + 'static {
     fn update(
         &mut self,
         update: BotUpdate,
     ) -> (Option<ActionWithId>, Vec<LogEntry>);
+
+    /// Serialize any state that should survive a hot reload of the bot
+    /// library. Returning `None` means the new instance starts from scratch.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Receives the state returned by [`Bot::save_state`] of the instance this
+    /// bot replaces after a hot reload.
+    fn restore_state(&mut self, _state: Vec<u8>) {}
 }
 
 #[derive(Debug, Clone, Component, Serialize, Deserialize)]
@@ -48,6 +65,8 @@ pub struct BotUpdate {
     pub tick: u32,
 
     pub bot_data: BotData,
+    /// Shared by every bot rather than copied
+    pub match_info: Arc<MatchInfo>,
 
     // Result from previous action
     pub in_progress_action: Option<ActionWithId>,
@@ -56,6 +75,10 @@ pub struct BotUpdate {
 
 pub type ActionId = u32;
 
+/// The id of the Noops the server submits in place of a bot's own action,
+/// such as after a crash. Bots should never use it.
+pub const SERVER_ACTION_ID: ActionId = ActionId::MAX;
+
 #[derive(Debug, Clone)]
 pub struct ActionWithId {
     pub id: ActionId,
@@ -182,7 +205,18 @@ impl FrameKind {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumCount, FromRepr)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumCount,
+    FromRepr,
+    Serialize,
+    Deserialize,
+)]
 #[repr(u8)]
 pub enum Subsystem {
     PlasmaRifle,
@@ -0,0 +1,190 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use bevy_ecs::system::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::{Item, Pos, Team};
+
+/// What a bot is told when it is created, so that it doesn't have to work
+/// the match out from its first `BotUpdate`
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    pub team: Team,
+    pub map_width: usize,
+    pub map_height: usize,
+    /// The name of the level, such as `symmetric`. Empty if the match didn't
+    /// start from a level.
+    pub level: String,
+    pub rules: Rules,
+    /// Different for every bot, and the same every time the match is run
+    /// with the same seed
+    pub seed: u64,
+    /// Tuning parameters from the command line, given as `key=value`
+    pub params: BTreeMap<String, String>,
+    pub match_info: Arc<MatchInfo>,
+}
+
+impl BotConfig {
+    /// The parameter `key`, or `None` if it wasn't given or doesn't parse
+    pub fn param<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.params.get(key)?.parse().ok()
+    }
+}
+
+/// The rules of the match, fixed while it runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    /// What a bot has to hold to win the match for its team
+    pub win_hold: HashMap<Item, u8>,
+    /// How long one update may take before it draws on the time bank
+    pub tick_budget: Duration,
+    /// How much longer than `tick_budget` updates may take over the match
+    pub time_bank: Duration,
+    /// How many times a bot may panic before it is disabled
+    pub max_crashes: usize,
+}
+
+/// What every bot is told about the match, the same for all of them and for
+/// the whole match
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Resource, Serialize, Deserialize,
+)]
+pub struct MatchInfo {
+    /// Parts of the map the level named
+    pub regions: Vec<Region>,
+}
+
+impl MatchInfo {
+    pub fn region(&self, name: &str) -> Option<&Region> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    /// The regions the bots of `team` start in
+    pub fn spawn_zones(&self, team: Team) -> impl Iterator<Item = &Region> {
+        self.regions
+            .iter()
+            .filter(move |region| region.kind == RegionKind::Spawn(team))
+    }
+
+    /// The regions that contain `pos`
+    pub fn regions_at(&self, pos: Pos) -> impl Iterator<Item = &Region> {
+        self.regions
+            .iter()
+            .filter(move |region| region.contains(pos))
+    }
+}
+
+/// A named rectangle of the map, such as a team's base or a contested mine,
+/// so that bots can head for map features without hard-coding coordinates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    pub name: String,
+    pub kind: RegionKind,
+    /// The bottom left cell
+    pub min: Pos,
+    /// The top right cell, inclusive
+    pub max: Pos,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegionKind {
+    /// An area that matters to the game or to the bots, like a capture zone
+    Zone,
+    /// A point to head for, usually a single cell
+    Waypoint,
+    /// Where the bots of a team start
+    Spawn(Team),
+}
+
+impl Region {
+    /// The cells between two opposite corners, in either order
+    pub fn new(
+        name: impl Into<String>,
+        kind: RegionKind,
+        corner: Pos,
+        opposite: Pos,
+    ) -> Self {
+        Region {
+            name: name.into(),
+            kind,
+            min: Pos((
+                corner.x().min(opposite.x()),
+                corner.y().min(opposite.y()),
+            )),
+            max: Pos((
+                corner.x().max(opposite.x()),
+                corner.y().max(opposite.y()),
+            )),
+        }
+    }
+
+    pub fn contains(&self, pos: Pos) -> bool {
+        (self.min.x()..=self.max.x()).contains(&pos.x())
+            && (self.min.y()..=self.max.y()).contains(&pos.y())
+    }
+
+    /// The middle cell, rounding down and left
+    pub fn center(&self) -> Pos {
+        Pos((
+            (self.min.x() + self.max.x()) / 2,
+            (self.min.y() + self.max.y()) / 2,
+        ))
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Pos> + '_ {
+        (self.min.x()..=self.max.x()).flat_map(move |x| {
+            (self.min.y()..=self.max.y()).map(move |y| Pos((x, y)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_in_any_order() {
+        let region =
+            Region::new("mine", RegionKind::Zone, Pos((5, 1)), Pos((2, 3)));
+        assert_eq!(region.min, Pos((2, 1)));
+        assert_eq!(region.max, Pos((5, 3)));
+        assert_eq!(region.cells().count(), 12);
+        assert_eq!(region.center(), Pos((3, 2)));
+    }
+
+    #[test]
+    fn params_parse_to_the_asked_type() {
+        let config = BotConfig {
+            team: Team::Player,
+            map_width: 10,
+            map_height: 10,
+            level: String::new(),
+            rules: Rules::default(),
+            seed: 0,
+            params: BTreeMap::from([
+                ("scouts".to_string(), "3".to_string()),
+                ("style".to_string(), "rush".to_string()),
+            ]),
+            match_info: Arc::default(),
+        };
+        assert_eq!(config.param::<u32>("scouts"), Some(3));
+        assert_eq!(config.param::<String>("style"), Some("rush".to_string()));
+        assert_eq!(config.param::<u32>("style"), None);
+        assert_eq!(config.param::<u32>("missing"), None);
+    }
+
+    #[test]
+    fn contains_its_edges() {
+        let region =
+            Region::new("base", RegionKind::Zone, Pos((2, 2)), Pos((4, 4)));
+        assert!(region.contains(Pos((2, 4))));
+        assert!(region.contains(Pos((4, 2))));
+        assert!(!region.contains(Pos((5, 3))));
+        assert!(!region.contains(Pos((3, 1))));
+    }
+}
@@ -182,7 +182,7 @@ pub enum Dir {
 }
 
 #[derive(
-    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display,
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display,
 )]
 pub enum Team {
     Player,